@@ -7,7 +7,7 @@ use std::io::Write;
 use std::path::Path;
 
 fn main() {
-    let file = unsafe { nx::File::open(&Path::new("Sound.nx")).unwrap() };
+    let file = nx::File::open(Path::new("Sound.nx")).unwrap();
     for node in file.root().iter() {
         let group = node.name();
         if &group[..3] != "Bgm" { continue }
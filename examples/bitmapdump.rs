@@ -3,30 +3,24 @@ extern crate nx;
 extern crate image;
 
 use image::{ImageBuffer, Rgba};
-use nx::node::{GenericNode};
+use nx::node::{GenericNode, Type};
+use nx::walk::{Walker};
 use std::fs::{create_dir};
 use std::path::{Path};
 
-fn recurse<'a>(node: nx::Node<'a>, name: &str) {
-    if let Some(bitmap) = node.bitmap() {
+fn main() {
+    let _ = create_dir("bitmap");
+    let file = nx::File::open(Path::new("Data.nx")).unwrap();
+    let mut walk = Walker::new().dtypes(&[Type::Bitmap]).paths(true).walk(file.root());
+    while let Some(node) = walk.next() {
+        let bitmap = node.bitmap().unwrap();
         let mut buf = vec![0; bitmap.len() as usize];
         bitmap.data(&mut buf);
         for chunk in buf.chunks_mut(4) {
-            let t = chunk[0];
-            chunk[0] = chunk[2];
-            chunk[2] = t;
+            chunk.swap(0, 2);
         }
         let img: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_vec(
             bitmap.width() as u32, bitmap.height() as u32, buf).unwrap();
-        img.save(format!("bitmap/{}.png", name)).unwrap();
+        img.save(format!("bitmap/Data.{}.png", walk.path().replace('/', "."))).unwrap();
     }
-    for child in node.iter() {
-        recurse(child, &format!("{}.{}", name, child.name()))
-    }
-}
-
-fn main() {
-    let _ = create_dir("bitmap");
-    let file = unsafe { nx::File::open(&Path::new("Data.nx")).unwrap() };
-    recurse(file.root(), "Data");
 }
@@ -0,0 +1,26 @@
+// Copyright © 2015-2018, Peter Atashian
+extern crate nx;
+
+use nx::bitmap::Compression;
+use nx::wz::{self, Encryption};
+use std::env::args;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+fn main() {
+    let args: Vec<String> = args().collect();
+    if args.len() != 4 {
+        println!("Usage: wz2nx <gms|kms|none> <input.wz> <output.nx>");
+        return
+    }
+    let encryption = match &*args[1] {
+        "gms" => Encryption::Gms,
+        "kms" => Encryption::Kms,
+        _ => Encryption::None,
+    };
+    let root = wz::read(Path::new(&args[2]), encryption).unwrap();
+    let builder = root.to_builder(Compression::High(9)).unwrap();
+    let out = BufWriter::new(File::create(&args[3]).unwrap());
+    builder.write(out).unwrap();
+}
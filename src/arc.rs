@@ -0,0 +1,197 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Owned node handles which do not borrow the file
+
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use audio::Audio;
+use bitmap::Bitmap;
+use file::{Error, File};
+use node::{GenericNode, Node, NodeId, Type};
+
+/// A node which keeps its file alive through an `Arc`, so it can be stored and sent between
+/// threads without borrowing the file.
+#[derive(Clone)]
+pub struct ArcNode {
+    file: Arc<File>,
+    id: NodeId,
+}
+
+impl ArcNode {
+    /// Gets the root node of the file.
+    #[inline]
+    pub fn root(file: Arc<File>) -> ArcNode {
+        ArcNode { file, id: NodeId::ROOT }
+    }
+    /// Gets the node with the specified id, or `None` if the id is not in the node table.
+    #[inline]
+    pub fn new(file: Arc<File>, id: NodeId) -> Option<ArcNode> {
+        if id.0 < file.node_count() {
+            Some(ArcNode { file, id })
+        } else {
+            None
+        }
+    }
+    /// Creates an owned handle to a borrowed node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is not from `file`.
+    #[inline]
+    pub fn from_node(file: &Arc<File>, node: Node) -> ArcNode {
+        assert!(std::ptr::eq(&**file, node.file()), "node is not from this file");
+        ArcNode { file: file.clone(), id: node.id() }
+    }
+    /// Borrows this node as a `Node`.
+    #[inline]
+    pub fn node(&self) -> Node<'_> {
+        unsafe { Node::construct(self.file.get_node(self.id.0), &self.file) }
+    }
+    /// Gets the file this node is from.
+    #[inline]
+    pub fn file(&self) -> &Arc<File> {
+        &self.file
+    }
+    /// Gets the id of this node, which is its index in the node table.
+    #[inline]
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+    /// Wraps another node from the same file.
+    #[inline]
+    fn wrap(&self, node: Node) -> ArcNode {
+        ArcNode { file: self.file.clone(), id: node.id() }
+    }
+    /// Gets whether or not the node is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.node().is_empty()
+    }
+    /// Gets the name of this node from the string table.
+    #[inline]
+    pub fn name(&self) -> &str {
+        self.node().name()
+    }
+    /// Gets an iterator over this node's children.
+    #[inline]
+    pub fn iter(&self) -> ArcNodes {
+        let data = unsafe { self.file.get_node(self.id.0) };
+        ArcNodes {
+            file: self.file.clone(),
+            next: data.children,
+            end: data.children + data.count as u32,
+        }
+    }
+    /// Gets the parent of this node, or `None` for the root node.
+    #[inline]
+    pub fn parent(&self) -> Option<ArcNode> {
+        self.node().parent().map(|node| self.wrap(node))
+    }
+    /// Gets the path of this node. See `Node::path`.
+    #[inline]
+    pub fn path(&self) -> String {
+        self.node().path()
+    }
+    /// Gets the child node of the specified name.
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<ArcNode> {
+        self.node().get(name).map(|node| self.wrap(node))
+    }
+    /// Gets the node at the specified path relative to this node. See `GenericNode::resolve`.
+    #[inline]
+    pub fn resolve(&self, path: &str) -> Option<ArcNode> {
+        self.node().resolve(path).map(|node| self.wrap(node))
+    }
+    /// Gets the node at the specified path relative to this node. See
+    /// `GenericNode::try_resolve`.
+    #[inline]
+    pub fn try_resolve(&self, path: &str) -> Result<ArcNode, Error> {
+        self.node().try_resolve(path).map(|node| self.wrap(node))
+    }
+    /// Gets the type of this node.
+    #[inline]
+    pub fn dtype(&self) -> Type {
+        self.node().dtype()
+    }
+    /// Gets the string value of this node. This will be `None` if the node is not a string node.
+    #[inline]
+    pub fn string(&self) -> Option<&str> {
+        self.node().string()
+    }
+    /// Gets the integer value of this node. This will be `None` if the node is not an integer
+    /// node.
+    #[inline]
+    pub fn integer(&self) -> Option<i64> {
+        self.node().integer()
+    }
+    /// Gets the float value of this node. This will be `None` if the node is not a float node.
+    #[inline]
+    pub fn float(&self) -> Option<f64> {
+        self.node().float()
+    }
+    /// Gets the vector value of this node. This will be `None` if the node is not a vector node.
+    #[inline]
+    pub fn vector(&self) -> Option<(i32, i32)> {
+        self.node().vector()
+    }
+    /// Gets the audio value of this node. This will be `None` if the node is not an audio node.
+    #[inline]
+    pub fn audio(&self) -> Option<Audio<'_>> {
+        self.node().audio()
+    }
+    /// Gets the bitmap value of this node. This will be `None` if the node is not a bitmap node.
+    #[inline]
+    pub fn bitmap(&self) -> Option<Bitmap<'_>> {
+        self.node().bitmap()
+    }
+}
+
+impl<'a> From<&'a ArcNode> for Node<'a> {
+    #[inline]
+    fn from(node: &'a ArcNode) -> Node<'a> {
+        node.node()
+    }
+}
+
+impl PartialEq for ArcNode {
+    #[inline]
+    fn eq(&self, other: &ArcNode) -> bool {
+        Arc::ptr_eq(&self.file, &other.file) && self.id == other.id
+    }
+}
+
+impl Eq for ArcNode {}
+
+impl Hash for ArcNode {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.file) as usize).hash(state);
+        self.id.hash(state);
+    }
+}
+
+/// An iterator over owned nodes.
+pub struct ArcNodes {
+    file: Arc<File>,
+    next: u32,
+    end: u32,
+}
+
+impl Iterator for ArcNodes {
+    type Item = ArcNode;
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.end - self.next) as usize;
+        (len, Some(len))
+    }
+    #[inline]
+    fn next(&mut self) -> Option<ArcNode> {
+        if self.next < self.end {
+            let id = NodeId(self.next);
+            self.next += 1;
+            Some(ArcNode { file: self.file.clone(), id })
+        } else {
+            None
+        }
+    }
+}
@@ -1,5 +1,18 @@
 // Copyright © 2015-2018, Peter Atashian
 //! Audio in NX files
+use std::convert::TryFrom;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+/// The format of the samples in some audio, as given by the wz audio header
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Format {
+    /// Uncompressed PCM samples
+    Pcm,
+    /// MPEG layer 3 frames
+    Mp3,
+    /// Some other `WAVEFORMATEX` format tag
+    Unknown(u16),
+}
 
 /// Some audio, possibly a sound effect or music
 #[derive(Clone, Copy)]
@@ -8,16 +21,71 @@ pub struct Audio<'a> {
 }
 impl<'a> Audio<'a> {
     /// Creates an Audio from the supplied data
+    ///
+    /// # Safety
+    ///
+    /// `data` must be the audio data of a node in an NX file.
     pub unsafe fn construct(data: &'a [u8]) -> Audio<'a> {
-        Audio { data: data }
+        Audio { data }
     }
     /// Returns the audio data, not including the wz audio header
-    pub fn data(&self) -> &[u8] {
-        &self.data[82..]
+    ///
+    /// This skips the actual length of the header, so for PCM audio it starts 12 bytes earlier
+    /// than it did in nx 0.3, which always skipped 82 bytes.
+    pub fn data(&self) -> &'a [u8] {
+        &self.data[self.header_len()..]
+    }
+    /// Returns the first 82 bytes of the audio, which is the wz audio header of MP3 audio
+    ///
+    /// For PCM audio this includes the first 12 bytes of the audio data, as its header is only 70
+    /// bytes long. Use `header_bytes` to get the header of any audio.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the audio is shorter than 82 bytes.
+    pub fn header(&self) -> &'a [u8; 82] {
+        <&[u8; 82]>::try_from(&self.data[..82]).unwrap()
+    }
+    /// Returns the wz audio header, which is 82 bytes for MP3 audio and 70 bytes for PCM audio
+    pub fn header_bytes(&self) -> &'a [u8] {
+        &self.data[..self.header_len()]
+    }
+    /// Returns the wz audio header followed by the audio data, as stored in the NX file
+    pub fn raw(&self) -> &'a [u8] {
+        self.data
+    }
+    /// Returns the `WAVEFORMATEX` structure at the end of the wz audio header, which is 18 bytes
+    /// for PCM audio and 30 bytes for MP3 audio
+    pub fn wave_format(&self) -> &'a [u8] {
+        &self.data[52.min(self.header_len())..self.header_len()]
     }
-    /// Returns the wz audio header
-    pub fn header(&self) -> &[u8; 82] {
-        assert!(self.data.len() >= 82);
-        unsafe { &*(self.data.as_ptr() as *const [u8; 82]) }
+    /// Returns the format of the audio data
+    pub fn format(&self) -> Format {
+        match *self.wave_format() {
+            [0x01, 0x00, ..] => Format::Pcm,
+            [0x55, 0x00, ..] => Format::Mp3,
+            [low, high, ..] => Format::Unknown(u16::from_le_bytes([low, high])),
+            _ => Format::Unknown(0),
+        }
+    }
+    /// The length of the wz audio header. The byte before the `WAVEFORMATEX` structure holds its
+    /// length, so the header is 82 bytes for MP3 audio but only 70 bytes for PCM audio.
+    fn header_len(&self) -> usize {
+        match self.data.get(51) {
+            Some(&len) => (52 + len as usize).min(self.data.len()),
+            None => self.data.len(),
+        }
+    }
+}
+impl<'a> Debug for Audio<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct("Audio").field("format", &self.format())
+            .field("len", &self.data().len()).finish()
+    }
+}
+/// Audio is equal if its header and data are byte for byte equal.
+impl<'a, 'b> PartialEq<Audio<'b>> for Audio<'a> {
+    fn eq(&self, other: &Audio<'b>) -> bool {
+        self.data == other.data
     }
 }
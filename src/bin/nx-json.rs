@@ -0,0 +1,62 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Exports a subtree of an NX file to JSON.
+extern crate nx;
+
+use nx::GenericNode;
+use nx::export::json::JsonOptions;
+use std::env::args;
+use std::fs::File;
+use std::io::stdout;
+use std::path::Path;
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: nx-json <file> [path] [--pretty] [--hash] [--data] [--output <file>]
+
+Writes the node at `path`, or the root node, and its descendants to standard output as JSON.
+
+Options:
+    --pretty         Indent the output
+    --hash           Include a SHA-256 hash of every bitmap and audio
+    --data           Include the contents of every bitmap and audio in base64
+    --output <file>  Write to a file instead of standard output";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2)
+}
+
+fn fail<T: std::fmt::Display>(err: T) -> ! {
+    eprintln!("nx-json: {}", err);
+    exit(1)
+}
+
+fn main() {
+    let mut options = JsonOptions::new();
+    let (mut file, mut path, mut output) = (None, None, None);
+    let mut args = args().skip(1);
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "--pretty" => { options.pretty(true); },
+            "--hash" => { options.hash(true); },
+            "--data" => { options.data(true); },
+            "--output" | "-o" => output = Some(args.next().unwrap_or_else(|| usage())),
+            "--help" | "-h" => usage(),
+            _ if file.is_none() => file = Some(arg),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let file = nx::OpenOptions::new().allow_unknown_types(true)
+        .open(Path::new(&file.unwrap_or_else(|| usage()))).unwrap_or_else(|e| fail(e));
+    let node = match path {
+        Some(path) => file.root().try_resolve(&path).unwrap_or_else(|e| fail(e)),
+        None => file.root(),
+    };
+    let result = match output {
+        Some(output) => File::create(output).map_err(nx::Error::from)
+            .and_then(|out| options.write(node, out)),
+        None => options.write(node, stdout().lock()),
+    };
+    result.unwrap_or_else(|e| fail(e));
+}
@@ -0,0 +1,242 @@
+// Copyright © 2015-2018, Peter Atashian
+//! A command-line tool for inspecting NX files.
+extern crate nx;
+
+use nx::walk::Walker;
+use nx::{GenericNode, Node, Type, Value};
+use std::collections::BTreeMap;
+use std::env::args;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write, stdout};
+use std::path::Path;
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: nx <command> <file> [arguments]
+
+Commands:
+    ls <file> [path]                  List the children of a node
+    tree <file> [path] [--depth N]    Print the tree below a node
+    cat <file> <path>                 Print the value of a node
+    stat <file>                       Print header counts and type histograms
+    find <file> <pattern> [path]      Print the paths of nodes whose name matches a glob
+
+Paths are separated by `/`. Patterns may contain `*`, `?` and `[...]`.";
+
+#[cfg(feature = "extract")]
+const EXTRACT_USAGE: &str = "
+    extract <file> <dir> [path] [--bitmaps | --audio] [--threads N] [--force]
+        Save the bitmaps and audio below a node as PNG, MP3 and WAV files in a directory
+        tree mirroring the nodes, along with a manifest. Existing files are skipped unless
+        --force is given.";
+#[cfg(not(feature = "extract"))]
+const EXTRACT_USAGE: &str = "";
+
+fn usage() -> ! {
+    eprintln!("{}\n{}", USAGE, EXTRACT_USAGE);
+    exit(2)
+}
+
+fn fail<T: std::fmt::Display>(err: T) -> ! {
+    eprintln!("nx: {}", err);
+    exit(1)
+}
+
+/// Formats the value of a node in a single line.
+fn value(node: Node) -> String {
+    match node.value() {
+        Value::String(string) => format!("{:?}", string),
+        value => value.to_string(),
+    }
+}
+
+/// Formats a node as its name, type, value and number of children.
+fn describe(node: Node) -> String {
+    let mut line = format!("{:<8} {}", format!("{:?}", node.dtype()), node.name());
+    let value = value(node);
+    if !value.is_empty() {
+        let _ = write!(line, " = {}", value);
+    }
+    let count = node.iter().len();
+    if count > 0 {
+        let _ = write!(line, " [{}]", count);
+    }
+    line
+}
+
+fn ls<W: Write>(out: &mut W, node: Node) {
+    for child in node.iter() {
+        writeln!(out, "{}", describe(child)).unwrap_or_else(|e| fail(e));
+    }
+}
+
+fn tree<W: Write>(out: &mut W, node: Node, max: Option<usize>) {
+    let mut walker = Walker::new();
+    if let Some(max) = max {
+        walker.max_depth(max);
+    }
+    let mut walk = walker.walk(node);
+    while let Some(child) = walk.next() {
+        writeln!(out, "{:indent$}{}", "", describe(child), indent = (walk.depth() - 1) * 2)
+            .unwrap_or_else(|e| fail(e));
+    }
+}
+
+fn cat<W: Write>(out: &mut W, node: Node) {
+    let result = match node.dtype() {
+        Type::String => writeln!(out, "{}", node.string().unwrap()),
+        _ => writeln!(out, "{}", value(node)),
+    };
+    result.unwrap_or_else(|e| fail(e));
+}
+
+fn stat<W: Write>(out: &mut W, file: &nx::File) -> std::io::Result<()> {
+    let info = file.header_info();
+    writeln!(out, "nodes     {:>10}  at {:#x}", info.node_count, info.node_offset)?;
+    writeln!(out, "strings   {:>10}  at {:#x}", info.string_count, info.string_offset)?;
+    writeln!(out, "bitmaps   {:>10}  at {:#x}", info.bitmap_count, info.bitmap_offset)?;
+    writeln!(out, "audio     {:>10}  at {:#x}", info.audio_count, info.audio_offset)?;
+    let mut types: BTreeMap<u16, (u64, u64)> =
+        (Type::Empty.raw()..=Type::Audio.raw()).map(|dtype| (dtype, (0, 0))).collect();
+    let mut max_depth = 0;
+    let mut walk = Walker::new().include_root(true).walk(file.root());
+    while let Some(node) = walk.next() {
+        max_depth = max_depth.max(walk.depth());
+        let counts = types.entry(node.raw_type()).or_default();
+        counts.0 += 1;
+        if !node.is_empty() { counts.1 += 1 }
+    }
+    writeln!(out, "depth     {:>10}", max_depth)?;
+    writeln!(out)?;
+    writeln!(out, "{:<8} {:>10} {:>10}", "type", "nodes", "parents")?;
+    for (&dtype, &(nodes, parents)) in &types {
+        let name = format!("{:?}", Type::from_raw(dtype));
+        writeln!(out, "{:<8} {:>10} {:>10}", name, nodes, parents)?;
+    }
+    Ok(())
+}
+
+/// Matches `name` against a glob pattern supporting `*`, `?` and `[...]` character classes.
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob(&pattern[1..], &name[1..]),
+        Some('[') => {
+            // A `]` right after the `[` or `[!` is part of the class rather than its end.
+            let start = match pattern.get(1) {
+                Some('!') | Some('^') => 2,
+                _ => 1,
+            };
+            let close = match pattern.iter().skip(start + 1).position(|&c| c == ']') {
+                Some(close) => close + start + 1,
+                None => return name.first() == Some(&'[') && glob(&pattern[1..], &name[1..]),
+            };
+            let class = &pattern[start..close];
+            let c = match name.first() {
+                Some(&c) => c,
+                None => return false,
+            };
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    found |= class[i] == c;
+                    i += 1;
+                }
+            }
+            found == (start == 1) && glob(&pattern[close + 1..], &name[1..])
+        },
+        Some(&c) => name.first() == Some(&c) && glob(&pattern[1..], &name[1..]),
+    }
+}
+
+fn find<W: Write>(out: &mut W, node: Node, pattern: &[char]) {
+    let prefix = node.path();
+    let mut walk = Walker::new().paths(true).walk(node);
+    while let Some(child) = walk.next() {
+        let name: Vec<char> = child.name().chars().collect();
+        if !glob(pattern, &name) { continue }
+        let result = match &*prefix {
+            "" => writeln!(out, "{}", walk.path()),
+            prefix => writeln!(out, "{}/{}", prefix, walk.path()),
+        };
+        result.unwrap_or_else(|e| fail(e));
+    }
+}
+
+#[cfg(feature = "extract")]
+fn extract<W: Write>(out: &mut W, root: Node, args: &[String]) {
+    use nx::extract::{ExtractOptions, Filter, Status};
+    let mut options = ExtractOptions::new();
+    let (mut dir, mut path) = (None, None);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &**arg {
+            "--bitmaps" => { options.filter(Filter::Bitmaps); },
+            "--audio" => { options.filter(Filter::Audio); },
+            "--force" => { options.overwrite(true); },
+            "--threads" | "-j" => {
+                let value = args.next().unwrap_or_else(|| usage());
+                options.threads(value.parse().unwrap_or_else(|e| fail(e)));
+            },
+            _ if dir.is_none() => dir = Some(arg),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let dir = Path::new(dir.unwrap_or_else(|| usage()));
+    let node = match path {
+        Some(path) => root.try_resolve(path).unwrap_or_else(|e| fail(e)),
+        None => root,
+    };
+    let manifest = options.extract(node, dir).unwrap_or_else(|e| fail(e));
+    let skipped = manifest.entries.iter().filter(|entry| entry.status == Status::Skipped).count();
+    writeln!(out, "{} written, {} skipped, manifest in {}", manifest.entries.len() - skipped,
+        skipped, dir.join(nx::extract::MANIFEST).display()).unwrap_or_else(|e| fail(e));
+}
+
+fn main() {
+    let args: Vec<String> = args().skip(1).collect();
+    if args.len() < 2 { usage() }
+    let file = nx::OpenOptions::new().allow_unknown_types(true).open(Path::new(&args[1]))
+        .unwrap_or_else(|e| fail(e));
+    let root = file.root();
+    let node = |path: Option<&String>| match path {
+        Some(path) => root.try_resolve(path).unwrap_or_else(|e| fail(e)),
+        None => root,
+    };
+    let out = stdout();
+    let mut out = BufWriter::new(out.lock());
+    match (&*args[0], &args[2..]) {
+        ("ls", rest) if rest.len() <= 1 => ls(&mut out, node(rest.first())),
+        ("tree", rest) => {
+            let (mut path, mut depth) = (None, None);
+            let mut rest = rest.iter();
+            while let Some(arg) = rest.next() {
+                match &**arg {
+                    "--depth" | "-d" => {
+                        let value = rest.next().unwrap_or_else(|| usage());
+                        depth = Some(value.parse().unwrap_or_else(|e| fail(e)));
+                    },
+                    _ if path.is_none() => path = Some(arg),
+                    _ => usage(),
+                }
+            }
+            tree(&mut out, node(path), depth);
+        },
+        ("cat", [path]) => cat(&mut out, node(Some(path))),
+        ("stat", []) => stat(&mut out, &file).unwrap_or_else(|e| fail(e)),
+        ("find", rest) if !rest.is_empty() && rest.len() <= 2 => {
+            let pattern: Vec<char> = rest[0].chars().collect();
+            find(&mut out, node(rest.get(1)), &pattern);
+        },
+        #[cfg(feature = "extract")]
+        ("extract", rest) => extract(&mut out, root, rest),
+        _ => usage(),
+    }
+    out.flush().unwrap_or_else(|e| fail(e));
+}
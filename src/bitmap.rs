@@ -1,6 +1,28 @@
 // Copyright © 2015-2018, Peter Atashian
 //! Bitmaps in NX files
-use lz4::{decompress};
+use lz4::{compress, decompress, MAX_HC_LEVEL, MIN_HC_LEVEL};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+#[cfg(feature = "png")]
+use png::{BitDepth, ColorType, Decoder, Encoder, HasParameters, Transformations};
+#[cfg(feature = "png")]
+use std::convert::TryFrom;
+#[cfg(feature = "png")]
+use std::io::{Error as IoError, ErrorKind, Read, Write};
+
+#[cfg(feature = "png")]
+use file::Error;
+
+/// How bitmap data is compressed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Compression {
+    /// The fast LZ4 compressor.
+    #[default]
+    Fast,
+    /// The LZ4HC compressor at a level from 1 to 12, where 9 is the level LZ4HC uses by default.
+    /// Higher levels take longer to compress but produce smaller data, and decompress just as
+    /// fast. A level of 0 is treated as 1, and levels above 12 as 12.
+    High(u8),
+}
 
 /// Represents a bitmap
 #[derive(Clone, Copy)]
@@ -19,12 +41,21 @@ impl<'a> Bitmap<'a> {
         self.height
     }
     /// The length of the data in bytes
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u32 {
         self.width as u32 * self.height as u32 * 4
     }
     /// Creates a `Bitmap` from the supplied data
+    ///
+    /// # Safety
+    ///
+    /// `data` must be the LZ4 compressed bitmap data of a node in an NX file.
     pub unsafe fn construct(data: &'a [u8], width: u16, height: u16) -> Bitmap<'a> {
-        Bitmap { width: width, height: height, data: data }
+        Bitmap { width, height, data }
+    }
+    /// The LZ4 compressed data, exactly as it is stored in the NX file
+    pub fn raw(&self) -> &'a [u8] {
+        self.data
     }
     /// Decompresses the bitmap data into the provided buffer
     pub fn data(&self, out: &mut [u8]) {
@@ -32,4 +63,90 @@ impl<'a> Bitmap<'a> {
         let len = decompress(self.data, out);
         assert_eq!(len, Ok(self.len() as usize));
     }
+    /// Compresses BGRA8 pixel data into the form bitmaps are stored in
+    pub fn compress(pixels: &[u8], width: u16, height: u16) -> Vec<u8> {
+        Bitmap::compress_with(pixels, width, height, Compression::Fast)
+    }
+    /// Compresses BGRA8 pixel data into the form bitmaps are stored in, using the specified
+    /// compression
+    pub fn compress_with(pixels: &[u8], width: u16, height: u16, compression: Compression)
+        -> Vec<u8> {
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+        match compression {
+            Compression::Fast => compress(pixels, None),
+            Compression::High(level) => {
+                compress(pixels, Some(level.clamp(MIN_HC_LEVEL, MAX_HC_LEVEL)))
+            },
+        }
+    }
+}
+impl<'a> Debug for Bitmap<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        fmt.debug_struct("Bitmap").field("width", &self.width).field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+/// Bitmaps are equal if they have the same size and pixels, even if their data was compressed
+/// differently.
+impl<'a, 'b> PartialEq<Bitmap<'b>> for Bitmap<'a> {
+    fn eq(&self, other: &Bitmap<'b>) -> bool {
+        if (self.width, self.height) != (other.width, other.height) {
+            return false
+        }
+        if self.data == other.data {
+            return true
+        }
+        let mut pixels = vec![0; self.len() as usize];
+        let mut others = vec![0; other.len() as usize];
+        self.data(&mut pixels);
+        other.data(&mut others);
+        pixels == others
+    }
+}
+#[cfg(feature = "png")]
+impl<'a> Bitmap<'a> {
+    /// Encodes the bitmap as an 8-bit RGBA PNG image
+    pub fn to_png<W: Write>(&self, out: W) -> Result<(), Error> {
+        let mut pixels = vec![0; self.len() as usize];
+        self.data(&mut pixels);
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+        let mut encoder = Encoder::new(out, self.width as u32, self.height as u32);
+        encoder.set(ColorType::RGBA).set(BitDepth::Eight);
+        encoder.write_header().and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(IoError::from)?;
+        Ok(())
+    }
+    /// Decodes an 8-bit PNG image into BGRA8 pixel data, returning the pixels, width and height
+    pub fn from_png<R: Read>(reader: R) -> Result<(Vec<u8>, u16, u16), Error> {
+        let invalid = |reason: String| Error::Io(IoError::new(ErrorKind::InvalidData, reason));
+        let mut decoder = Decoder::new(reader);
+        decoder.set(Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info().map_err(|e| invalid(e.to_string()))?;
+        let (width, height) = match (u16::try_from(info.width), u16::try_from(info.height)) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(invalid(format!("{}x{} image is too large", info.width,
+                info.height))),
+        };
+        let mut buf = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buf).map_err(|e| invalid(e.to_string()))?;
+        let samples = match info.bit_depth {
+            BitDepth::Eight => info.color_type.samples(),
+            _ => 0,
+        };
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for sample in buf.chunks(samples.max(1)) {
+            match *sample {
+                [r, g, b, a] => pixels.extend_from_slice(&[b, g, r, a]),
+                [r, g, b] => pixels.extend_from_slice(&[b, g, r, 255]),
+                [l, a] => pixels.extend_from_slice(&[l, l, l, a]),
+                [l] if samples == 1 => pixels.extend_from_slice(&[l, l, l, 255]),
+                _ => return Err(invalid(format!("{:?} images with {:?} samples are not supported",
+                    info.color_type, info.bit_depth))),
+            }
+        }
+        pixels.truncate(width as usize * height as usize * 4);
+        Ok((pixels, width, height))
+    }
 }
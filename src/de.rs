@@ -0,0 +1,365 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Deserializing Rust types from NX nodes with serde
+//!
+//! Nodes map onto the serde data model as follows:
+//!
+//! * Structs and maps are read from the children of a node, keyed by name. Struct fields are
+//!   looked up by name, so children which are not fields are ignored, and `Option` fields are
+//!   `None` when their child does not exist.
+//! * Sequences are read from the children with numeric names, in numeric order. Other children
+//!   are ignored. A gap in the numbering is read as a missing element, which is `None` for
+//!   `Option` elements and an error otherwise, so a `Vec<Option<T>>` keeps its positions.
+//! * Integer nodes can be read as any integer type which their value fits in, as floats, and as
+//!   `bool`, where zero is `false`. Float nodes can be read as floats.
+//! * String nodes are borrowed from the string table, so `&'a str` fields do not copy them.
+//! * Vector nodes can be read as two element tuples or sequences, or as structs with `x` and `y`
+//!   fields.
+//! * Audio nodes can be read as bytes, which are borrowed and include the wz audio header.
+//!   Bitmap nodes can be read as bytes, which are the decompressed BGRA8 pixels.
+//! * Enums are read from string nodes for unit variants, or from nodes with a single child named
+//!   after the variant.
+//!
+//! Errors name the path of the node which could not be deserialized.
+use serde::de::{self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor};
+use serde::Deserialize;
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use file::Error;
+use node::{GenericNode, Node, Nodes, Type};
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::Deserialize { path: None, reason: msg.to_string() }
+    }
+}
+
+/// Deserializes an instance of `T` from `node` and its descendants.
+pub fn from_node<'a, T: Deserialize<'a>>(node: Node<'a>) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(node))
+}
+
+/// A deserializer reading from a node and its descendants.
+#[derive(Clone, Copy)]
+pub struct Deserializer<'a> {
+    node: Node<'a>,
+}
+
+impl<'a> Deserializer<'a> {
+    /// Creates a deserializer reading from `node`.
+    pub fn new(node: Node<'a>) -> Deserializer<'a> {
+        Deserializer { node }
+    }
+    /// Names this node in an error which does not name a node yet. Errors from descendants
+    /// already name the descendant, so they are left alone.
+    fn locate<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        result.map_err(|err| match err {
+            Error::Deserialize { path: None, reason } => Error::Deserialize {
+                path: Some(self.node.path()),
+                reason,
+            },
+            err => err,
+        })
+    }
+    fn mismatch<T>(&self, expected: &str) -> Result<T, Error> {
+        self.locate(Err(de::Error::custom(format_args!("expected {}, found {:?} node", expected,
+            self.node.dtype()))))
+    }
+    /// The children with numeric names, in numeric order.
+    fn elements(&self) -> Elements<'a> {
+        let mut elements: Vec<(u64, Node<'a>)> = self.node.iter()
+            .filter_map(|child| child.name().parse().ok().map(|index| (index, child)))
+            .collect();
+        elements.sort_by_key(|&(index, _)| index);
+        Elements { node: self.node, elements: elements.into_iter().peekable(), index: 0 }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let node = self.node;
+        let result = match node.dtype() {
+            Type::Empty if node.is_empty() => visitor.visit_unit(),
+            Type::Empty => visitor.visit_map(Children { nodes: node.iter(), value: None }),
+            Type::Integer => visitor.visit_i64(node.integer().unwrap()),
+            Type::Float => visitor.visit_f64(node.float().unwrap()),
+            Type::String => visitor.visit_borrowed_str(node.string().unwrap()),
+            Type::Vector => {
+                let (x, y) = node.vector().unwrap();
+                visitor.visit_seq(Coordinates { values: [x, y], index: 0 })
+            },
+            Type::Bitmap | Type::Audio => return self.deserialize_bytes(visitor),
+            Type::Unknown(dtype) => Err(de::Error::custom(format_args!(
+                "the node has unknown type {}", dtype))),
+        };
+        self.locate(result)
+    }
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node.integer() {
+            Some(value) => self.locate(visitor.visit_bool(value != 0)),
+            None => self.mismatch("an integer"),
+        }
+    }
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Some(audio) = self.node.audio() {
+            return self.locate(visitor.visit_borrowed_bytes(audio.raw()))
+        }
+        match self.node.bitmap() {
+            Some(bitmap) => {
+                let mut pixels = vec![0; bitmap.len() as usize];
+                bitmap.data(&mut pixels);
+                self.locate(visitor.visit_byte_buf(pixels))
+            },
+            None => self.mismatch("a bitmap or audio"),
+        }
+    }
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.locate(visitor.visit_some(self))
+    }
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.locate(visitor.visit_unit())
+    }
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+        -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+        -> Result<V::Value, Error> {
+        self.locate(visitor.visit_newtype_struct(self))
+    }
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if let Some((x, y)) = self.node.vector() {
+            return self.locate(visitor.visit_seq(Coordinates { values: [x, y], index: 0 }))
+        }
+        self.locate(visitor.visit_seq(self.elements()))
+    }
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V)
+        -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize,
+        visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.locate(visitor.visit_map(Children { nodes: self.node.iter(), value: None }))
+    }
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str,
+        fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        if let Some((x, y)) = self.node.vector() {
+            return self.locate(visitor.visit_map(Point { values: [x, y], index: 0 }))
+        }
+        self.locate(visitor.visit_map(Fields { node: self.node, fields, value: None }))
+    }
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str,
+        _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        if let Some(variant) = self.node.string() {
+            return self.locate(visitor.visit_enum(variant.into_deserializer()))
+        }
+        let mut children = self.node.iter();
+        match (children.next(), children.next()) {
+            (Some(child), None) => self.locate(visitor.visit_enum(Variant { node: child })),
+            _ => self.mismatch("a string or a node with a single child"),
+        }
+    }
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string identifier
+    }
+}
+
+/// The children of a node as a map from their names to themselves.
+struct Children<'a> {
+    nodes: Nodes<'a>,
+    value: Option<Node<'a>>,
+}
+
+impl<'de> MapAccess<'de> for Children<'de> {
+    type Error = Error;
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K)
+        -> Result<Option<K::Value>, Error> {
+        match self.nodes.next() {
+            Some(child) => {
+                self.value = Some(child);
+                let name: de::value::BorrowedStrDeserializer<Error> =
+                    de::value::BorrowedStrDeserializer::new(child.name());
+                seed.deserialize(name).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let child = self.value.take().expect("next_value_seed called before next_key_seed");
+        seed.deserialize(Deserializer::new(child))
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+}
+
+/// The children of a node which are named after the fields of a struct.
+struct Fields<'a> {
+    node: Node<'a>,
+    fields: &'static [&'static str],
+    value: Option<Node<'a>>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'de> {
+    type Error = Error;
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K)
+        -> Result<Option<K::Value>, Error> {
+        while let Some((&field, rest)) = self.fields.split_first() {
+            self.fields = rest;
+            if let Some(child) = self.node.get(field) {
+                self.value = Some(child);
+                return seed.deserialize(field.into_deserializer()).map(Some)
+            }
+        }
+        Ok(None)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let child = self.value.take().expect("next_value_seed called before next_key_seed");
+        seed.deserialize(Deserializer::new(child))
+    }
+}
+
+/// The children of a node with numeric names, in numeric order.
+struct Elements<'a> {
+    node: Node<'a>,
+    elements: Peekable<IntoIter<(u64, Node<'a>)>>,
+    /// The index of the next element.
+    index: u64,
+}
+
+impl<'de> SeqAccess<'de> for Elements<'de> {
+    type Error = Error;
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T)
+        -> Result<Option<T::Value>, Error> {
+        let index = self.index;
+        match self.elements.peek() {
+            Some(&(next, child)) if next <= index => {
+                self.elements.next();
+                self.index = next + 1;
+                seed.deserialize(Deserializer::new(child)).map(Some)
+            },
+            Some(_) => {
+                self.index += 1;
+                seed.deserialize(Missing { node: self.node, index }).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+/// An element missing from a gap in the numbering of the children of `node`.
+struct Missing<'a> {
+    node: Node<'a>,
+    index: u64,
+}
+
+impl<'de> de::Deserializer<'de> for Missing<'de> {
+    type Error = Error;
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        let mut path = self.node.path();
+        if !path.is_empty() { path.push('/') }
+        path.push_str(&self.index.to_string());
+        Err(Error::Deserialize { path: Some(path), reason: "the element is missing".to_owned() })
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_none()
+    }
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+/// The coordinates of a vector node as a sequence.
+struct Coordinates {
+    values: [i32; 2],
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> for Coordinates {
+    type Error = Error;
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T)
+        -> Result<Option<T::Value>, Error> {
+        match self.values.get(self.index) {
+            Some(&value) => {
+                self.index += 1;
+                seed.deserialize(value.into_deserializer()).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len() - self.index)
+    }
+}
+
+/// The coordinates of a vector node as a struct with `x` and `y` fields.
+struct Point {
+    values: [i32; 2],
+    index: usize,
+}
+
+impl<'de> MapAccess<'de> for Point {
+    type Error = Error;
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K)
+        -> Result<Option<K::Value>, Error> {
+        match ["x", "y"].get(self.index) {
+            Some(&key) => seed.deserialize(key.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.values[self.index];
+        self.index += 1;
+        seed.deserialize(value.into_deserializer())
+    }
+}
+
+/// The single child of a node, naming an enum variant and holding its contents.
+struct Variant<'a> {
+    node: Node<'a>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V)
+        -> Result<(V::Value, Deserializer<'de>), Error> {
+        let name: de::value::BorrowedStrDeserializer<Error> =
+            de::value::BorrowedStrDeserializer::new(self.node.name());
+        Ok((seed.deserialize(name)?, Deserializer::new(self.node)))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V)
+        -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
@@ -0,0 +1,272 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Exporting NX subtrees to JSON
+//!
+//! Each node becomes a JSON value according to its type:
+//!
+//! * Empty nodes become objects keyed by the names of their children.
+//! * Integers and floats become numbers. Floats always have a fractional part, so `1.0` stays a
+//!   float, and floats which are not finite become `null`.
+//! * Strings become strings.
+//! * Vectors become `{"type": "vector", "x": x, "y": y}`.
+//! * Bitmaps become `{"type": "bitmap", "width": w, "height": h, "length": n}` where `n` is the
+//!   length of the decompressed BGRA8 pixels.
+//! * Audio becomes `{"type": "audio", "length": n}` where `n` is the length of the wz audio header
+//!   and the audio data.
+//! * Nodes of unknown types become `{"type": "unknown", "dtype": t, "data": d}` where `t` is the
+//!   raw type and `d` is the raw data of the node in base64.
+//!
+//! Bitmap and audio objects can also have a `"sha256"` hash of the pixels or audio, and a
+//! `"data"` string with the same bytes in base64.
+//!
+//! A node which has both a value and children becomes an object keyed by the names of its
+//! children, with the value under the extra key `"$value"`. So that no node with children can be
+//! mistaken for one of the objects above, an empty node with a child named `type` also gets a
+//! `"$value"` of `null`. A child named `$value`, or with a name starting with `$$`, is written
+//! with an extra `$` in front of its name.
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use base64::write::EncoderWriter;
+use sha2::{Digest, Sha256};
+use std::io::{BufWriter, Write};
+
+use file::Error;
+use node::{GenericNode, Node, Type};
+
+/// The key a node's value is stored under when the node also has children.
+pub const VALUE_KEY: &str = "$value";
+
+/// Options for exporting a subtree to JSON.
+#[derive(Clone, Debug, Default)]
+pub struct JsonOptions {
+    pretty: bool,
+    hash: bool,
+    data: bool,
+}
+
+impl JsonOptions {
+    /// Creates options for compact output with only the metadata of bitmaps and audio.
+    pub fn new() -> JsonOptions {
+        JsonOptions::default()
+    }
+    /// Sets whether the output is indented with two spaces per level.
+    pub fn pretty(&mut self, pretty: bool) -> &mut JsonOptions {
+        self.pretty = pretty;
+        self
+    }
+    /// Sets whether bitmaps and audio include a SHA-256 hash of their contents, which is
+    /// useful for noticing changes without including the contents.
+    pub fn hash(&mut self, hash: bool) -> &mut JsonOptions {
+        self.hash = hash;
+        self
+    }
+    /// Sets whether bitmaps and audio include their contents in base64.
+    pub fn data(&mut self, data: bool) -> &mut JsonOptions {
+        self.data = data;
+        self
+    }
+    /// Writes `node` and its descendants to `out` as they are visited, so the memory used does
+    /// not depend on the size of the subtree.
+    pub fn write<W: Write>(&self, node: Node, out: W) -> Result<(), Error> {
+        let mut writer = Writer { options: self, out: BufWriter::new(out), depth: 0 };
+        writer.node(node)?;
+        if self.pretty {
+            writer.out.write_all(b"\n")?;
+        }
+        writer.out.flush()?;
+        Ok(())
+    }
+    /// Exports `node` and its descendants to a string.
+    pub fn to_string(&self, node: Node) -> Result<String, Error> {
+        let mut out = Vec::new();
+        self.write(node, &mut out)?;
+        Ok(String::from_utf8(out).expect("JSON output is always UTF-8"))
+    }
+}
+
+/// Writes `node` and its descendants to `out` as compact JSON with default options.
+pub fn to_writer<W: Write>(node: Node, out: W) -> Result<(), Error> {
+    JsonOptions::new().write(node, out)
+}
+
+/// Exports `node` and its descendants to a compact JSON string with default options.
+pub fn to_string(node: Node) -> Result<String, Error> {
+    JsonOptions::new().to_string(node)
+}
+
+struct Writer<'a, W: Write> {
+    options: &'a JsonOptions,
+    out: BufWriter<W>,
+    depth: usize,
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    fn node(&mut self, node: Node) -> Result<(), Error> {
+        if node.dtype() != Type::Empty && node.is_empty() {
+            return self.value(node)
+        }
+        self.open()?;
+        let mut first = true;
+        if node.dtype() != Type::Empty || node.get("type").is_some() {
+            self.key(VALUE_KEY, &mut first)?;
+            self.value(node)?;
+        }
+        for child in node.iter() {
+            let name = child.name();
+            if name == VALUE_KEY || name.starts_with("$$") {
+                self.key(&format!("${}", name), &mut first)?;
+            } else {
+                self.key(name, &mut first)?;
+            }
+            self.node(child)?;
+        }
+        self.close(first)
+    }
+    fn value(&mut self, node: Node) -> Result<(), Error> {
+        match node.dtype() {
+            Type::Empty => self.out.write_all(b"null")?,
+            Type::Integer => write!(self.out, "{}", node.integer().unwrap())?,
+            Type::Float => self.float(node.float().unwrap())?,
+            Type::String => self.string(node.string().unwrap())?,
+            Type::Vector => {
+                let (x, y) = node.vector().unwrap();
+                let mut first = true;
+                self.open()?;
+                self.key("type", &mut first)?;
+                self.string("vector")?;
+                self.key("x", &mut first)?;
+                write!(self.out, "{}", x)?;
+                self.key("y", &mut first)?;
+                write!(self.out, "{}", y)?;
+                self.close(first)?;
+            },
+            Type::Bitmap => {
+                let bitmap = node.bitmap().unwrap();
+                let mut first = true;
+                self.open()?;
+                self.key("type", &mut first)?;
+                self.string("bitmap")?;
+                self.key("width", &mut first)?;
+                write!(self.out, "{}", bitmap.width())?;
+                self.key("height", &mut first)?;
+                write!(self.out, "{}", bitmap.height())?;
+                self.key("length", &mut first)?;
+                write!(self.out, "{}", bitmap.len())?;
+                if self.options.hash || self.options.data {
+                    let mut pixels = vec![0; bitmap.len() as usize];
+                    bitmap.data(&mut pixels);
+                    self.contents(&pixels, &mut first)?;
+                }
+                self.close(first)?;
+            },
+            Type::Audio => {
+                let audio = node.audio().unwrap();
+                let mut first = true;
+                self.open()?;
+                self.key("type", &mut first)?;
+                self.string("audio")?;
+                self.key("length", &mut first)?;
+                write!(self.out, "{}", audio.raw().len())?;
+                self.contents(audio.raw(), &mut first)?;
+                self.close(first)?;
+            },
+            Type::Unknown(dtype) => {
+                let mut first = true;
+                self.open()?;
+                self.key("type", &mut first)?;
+                self.string("unknown")?;
+                self.key("dtype", &mut first)?;
+                write!(self.out, "{}", dtype)?;
+                self.key("data", &mut first)?;
+                self.string(&STANDARD.encode(node.raw_data()))?;
+                self.close(first)?;
+            },
+        }
+        Ok(())
+    }
+    fn contents(&mut self, bytes: &[u8], first: &mut bool) -> Result<(), Error> {
+        if self.options.hash {
+            self.key("sha256", first)?;
+            self.out.write_all(b"\"")?;
+            for byte in Sha256::digest(bytes) {
+                write!(self.out, "{:02x}", byte)?;
+            }
+            self.out.write_all(b"\"")?;
+        }
+        if self.options.data {
+            self.key("data", first)?;
+            self.out.write_all(b"\"")?;
+            {
+                let mut encoder = EncoderWriter::new(&mut self.out, &STANDARD);
+                encoder.write_all(bytes)?;
+                encoder.finish()?;
+            }
+            self.out.write_all(b"\"")?;
+        }
+        Ok(())
+    }
+    fn float(&mut self, value: f64) -> Result<(), Error> {
+        if !value.is_finite() {
+            self.out.write_all(b"null")?;
+        } else if value.fract() == 0.0 && value.abs() < 1e16 {
+            write!(self.out, "{:.1}", value)?;
+        } else {
+            write!(self.out, "{:?}", value)?;
+        }
+        Ok(())
+    }
+    fn string(&mut self, value: &str) -> Result<(), Error> {
+        self.out.write_all(b"\"")?;
+        let mut start = 0;
+        for (index, byte) in value.bytes().enumerate() {
+            let escape: &[u8] = match byte {
+                b'"' => b"\\\"",
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                0x00..=0x1f => b"",
+                _ => continue,
+            };
+            self.out.write_all(&value.as_bytes()[start..index])?;
+            if escape.is_empty() {
+                write!(self.out, "\\u{:04x}", byte)?;
+            } else {
+                self.out.write_all(escape)?;
+            }
+            start = index + 1;
+        }
+        self.out.write_all(&value.as_bytes()[start..])?;
+        self.out.write_all(b"\"")?;
+        Ok(())
+    }
+    fn open(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        self.out.write_all(b"{")?;
+        Ok(())
+    }
+    /// Writes the separator before a key, the key itself and the colon after it.
+    fn key(&mut self, key: &str, first: &mut bool) -> Result<(), Error> {
+        if !*first {
+            self.out.write_all(b",")?;
+        }
+        *first = false;
+        self.newline()?;
+        self.string(key)?;
+        self.out.write_all(if self.options.pretty { b": " } else { b":" })?;
+        Ok(())
+    }
+    fn close(&mut self, empty: bool) -> Result<(), Error> {
+        self.depth -= 1;
+        if !empty {
+            self.newline()?;
+        }
+        self.out.write_all(b"}")?;
+        Ok(())
+    }
+    fn newline(&mut self) -> Result<(), Error> {
+        if self.options.pretty {
+            write!(self.out, "\n{:indent$}", "", indent = self.depth * 2)?;
+        }
+        Ok(())
+    }
+}
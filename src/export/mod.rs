@@ -0,0 +1,6 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Exporting NX subtrees to other formats
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "xml")]
+pub mod xml;
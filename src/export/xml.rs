@@ -0,0 +1,212 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Exporting NX subtrees to the WZ-XML dialect used by HaRepacker and WzDumper
+//!
+//! Each node becomes an element with a `name` attribute, and its children become child elements:
+//!
+//! * Empty nodes become `<imgdir>`.
+//! * Integers become `<int>`, or `<long>` if they do not fit in 32 bits.
+//! * Floats become `<float>` if they are exactly representable in 32 bits, or `<double>`.
+//! * Strings become `<string>`.
+//! * Vectors become `<vector>` with `x` and `y` attributes.
+//! * Bitmaps become `<canvas>` with `width` and `height` attributes, and optionally the image as
+//!   a base64 PNG in `basedata`.
+//! * Audio becomes `<sound>` with its duration in milliseconds in `length`, and optionally the wz
+//!   audio header and the audio data in base64 in `basehead` and `basedata`.
+//! * Nodes of unknown types become `<unknown>` with the raw type in `type` and the raw data in
+//!   base64 in `data`. Other tools do not know this element.
+//!
+//! XML 1.0 has no way to write most control characters, even escaped, so a name or string
+//! containing one other than tab, line feed and carriage return is rejected with
+//! `Error::XmlCharacter`.
+use base64::engine::general_purpose::STANDARD;
+use base64::write::EncoderWriter;
+use std::io::{BufWriter, Write};
+
+use audio::Audio;
+use file::Error;
+use node::{GenericNode, Node, Type};
+
+/// Options for exporting a subtree to XML.
+#[derive(Clone, Debug)]
+pub struct XmlOptions {
+    indent: bool,
+    data: bool,
+}
+
+impl XmlOptions {
+    /// Creates options for indented output without the contents of bitmaps and audio.
+    pub fn new() -> XmlOptions {
+        XmlOptions { indent: true, data: false }
+    }
+    /// Sets whether each element is on its own line, indented with two spaces per level.
+    pub fn indent(&mut self, indent: bool) -> &mut XmlOptions {
+        self.indent = indent;
+        self
+    }
+    /// Sets whether canvases and sounds include their contents in `basedata` and `basehead`,
+    /// which community tools need in order to import them.
+    pub fn data(&mut self, data: bool) -> &mut XmlOptions {
+        self.data = data;
+        self
+    }
+    /// Writes an XML document to `out` with `node` as its root element. Each element is written
+    /// as soon as its node is visited, so at most one canvas is held as a PNG at a time.
+    pub fn write<W: Write>(&self, node: Node, out: W) -> Result<(), Error> {
+        let mut out = BufWriter::new(out);
+        out.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>")?;
+        self.node(&mut out, node, 0)?;
+        if self.indent {
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+    /// Exports an XML document with `node` as its root element to a string.
+    pub fn to_string(&self, node: Node) -> Result<String, Error> {
+        let mut out = Vec::new();
+        self.write(node, &mut out)?;
+        Ok(String::from_utf8(out).expect("XML output is always UTF-8"))
+    }
+    fn node<W: Write>(&self, out: &mut W, node: Node, depth: usize) -> Result<(), Error> {
+        self.newline(out, depth)?;
+        let tag = match node.dtype() {
+            Type::Empty => "imgdir",
+            Type::Integer => match node.integer().unwrap() {
+                value if value as i32 as i64 == value => "int",
+                _ => "long",
+            },
+            Type::Float => match node.float().unwrap() {
+                value if value as f32 as f64 == value || value.is_nan() => "float",
+                _ => "double",
+            },
+            Type::String => "string",
+            Type::Vector => "vector",
+            Type::Bitmap => "canvas",
+            Type::Audio => "sound",
+            Type::Unknown(_) => "unknown",
+        };
+        write!(out, "<{}", tag)?;
+        attribute(out, node, "name", node.name())?;
+        match node.dtype() {
+            Type::Empty => (),
+            Type::Integer => write!(out, " value=\"{}\"", node.integer().unwrap())?,
+            Type::Float if tag == "float" => {
+                write!(out, " value=\"{}\"", node.float().unwrap() as f32)?
+            },
+            Type::Float => write!(out, " value=\"{}\"", node.float().unwrap())?,
+            Type::String => attribute(out, node, "value", node.string().unwrap())?,
+            Type::Vector => {
+                let (x, y) = node.vector().unwrap();
+                write!(out, " x=\"{}\" y=\"{}\"", x, y)?;
+            },
+            Type::Bitmap => {
+                let bitmap = node.bitmap().unwrap();
+                write!(out, " width=\"{}\" height=\"{}\"", bitmap.width(), bitmap.height())?;
+                if self.data {
+                    let mut png = Vec::new();
+                    bitmap.to_png(&mut png)?;
+                    base64(out, "basedata", &png)?;
+                }
+            },
+            Type::Audio => {
+                let audio = node.audio().unwrap();
+                write!(out, " length=\"{}\"", duration(audio))?;
+                if self.data {
+                    base64(out, "basehead", audio.header_bytes())?;
+                    base64(out, "basedata", audio.data())?;
+                }
+            },
+            Type::Unknown(dtype) => {
+                write!(out, " type=\"{}\"", dtype)?;
+                base64(out, "data", &node.raw_data())?;
+            },
+        }
+        if node.is_empty() {
+            out.write_all(b"/>")?;
+            return Ok(())
+        }
+        out.write_all(b">")?;
+        for child in node.iter() {
+            self.node(out, child, depth + 1)?;
+        }
+        self.newline(out, depth)?;
+        write!(out, "</{}>", tag)?;
+        Ok(())
+    }
+    fn newline<W: Write>(&self, out: &mut W, depth: usize) -> Result<(), Error> {
+        if self.indent {
+            write!(out, "\n{:indent$}", "", indent = depth * 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for XmlOptions {
+    fn default() -> XmlOptions {
+        XmlOptions::new()
+    }
+}
+
+/// Writes an indented XML document with `node` as its root element to `out`, with default
+/// options.
+pub fn to_writer<W: Write>(node: Node, out: W) -> Result<(), Error> {
+    XmlOptions::new().write(node, out)
+}
+
+/// Exports an indented XML document with `node` as its root element to a string, with default
+/// options.
+pub fn to_string(node: Node) -> Result<String, Error> {
+    XmlOptions::new().to_string(node)
+}
+
+/// Works out the duration of audio in milliseconds from the average number of bytes per second
+/// in its `WAVEFORMATEX`, which is exact for PCM and constant bitrate MP3 audio.
+fn duration(audio: Audio) -> u64 {
+    match *audio.wave_format() {
+        [_, _, _, _, _, _, _, _, a, b, c, d, ..] => match u32::from_le_bytes([a, b, c, d]) {
+            0 => 0,
+            rate => audio.data().len() as u64 * 1000 / rate as u64,
+        },
+        _ => 0,
+    }
+}
+
+/// Writes an attribute of the element for `node`, escaping its value.
+fn attribute<W: Write>(out: &mut W, node: Node, name: &str, value: &str) -> Result<(), Error> {
+    let invalid = value.chars().find(|&c| matches!(c,
+        '\u{0}'..='\u{8}' | '\u{B}' | '\u{C}' | '\u{E}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}'));
+    if let Some(character) = invalid {
+        return Err(Error::XmlCharacter { path: node.path(), character })
+    }
+    write!(out, " {}=\"", name)?;
+    let mut start = 0;
+    for (index, byte) in value.bytes().enumerate() {
+        let escape: &[u8] = match byte {
+            b'&' => b"&amp;",
+            b'<' => b"&lt;",
+            b'>' => b"&gt;",
+            b'"' => b"&quot;",
+            b'\t' => b"&#9;",
+            b'\n' => b"&#10;",
+            b'\r' => b"&#13;",
+            _ => continue,
+        };
+        out.write_all(&value.as_bytes()[start..index])?;
+        out.write_all(escape)?;
+        start = index + 1;
+    }
+    out.write_all(&value.as_bytes()[start..])?;
+    out.write_all(b"\"")?;
+    Ok(())
+}
+
+fn base64<W: Write>(out: &mut W, name: &str, bytes: &[u8]) -> Result<(), Error> {
+    write!(out, " {}=\"", name)?;
+    {
+        let mut encoder = EncoderWriter::new(&mut *out, &STANDARD);
+        encoder.write_all(bytes)?;
+        encoder.finish()?;
+    }
+    out.write_all(b"\"")?;
+    Ok(())
+}
@@ -0,0 +1,310 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Extracting bitmaps and audio from NX files into a directory tree
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::{self, File as FsFile};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use audio::{Audio, Format};
+use file::Error;
+use node::{GenericNode, Node, Type};
+use walk::Walker;
+
+/// The name of the manifest written into the output directory.
+pub const MANIFEST: &str = "manifest.tsv";
+
+/// Which nodes are extracted.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Filter {
+    /// Only bitmap nodes, which are saved as PNG images.
+    Bitmaps,
+    /// Only audio nodes, which are saved as MP3 or WAV files depending on their format.
+    Audio,
+    /// Both bitmap and audio nodes.
+    #[default]
+    All,
+}
+
+impl Filter {
+    fn matches(self, dtype: Type) -> bool {
+        matches!((self, dtype), (Filter::Bitmaps, Type::Bitmap) | (Filter::Audio, Type::Audio)
+            | (Filter::All, Type::Bitmap) | (Filter::All, Type::Audio))
+    }
+}
+
+/// What happened to a node during extraction.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Status {
+    /// The file was written.
+    Written,
+    /// The file already existed and was left alone.
+    Skipped,
+}
+
+/// A node which was extracted.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// The path of the node in the NX file.
+    pub path: String,
+    /// The path of the extracted file, relative to the output directory.
+    pub file: PathBuf,
+    /// The type of the node.
+    pub dtype: Type,
+    /// What happened to the node.
+    pub status: Status,
+}
+
+/// The nodes which were extracted, in the order they appear in the NX file.
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    /// One entry per extracted node.
+    pub entries: Vec<Entry>,
+}
+
+impl Manifest {
+    /// Writes the manifest as tab separated values, with one line per entry after a header line.
+    /// Backslashes, tabs and line breaks in node paths are escaped as `\\`, `\t`, `\n` and `\r`.
+    /// File paths never contain tabs or line breaks, as those are replaced when sanitizing.
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "file\tnode\ttype\tstatus")?;
+        for entry in &self.entries {
+            writeln!(out, "{}\t{}\t{:?}\t{:?}", entry.file.display(), escape(&entry.path),
+                entry.dtype, entry.status)?;
+        }
+        out.flush()
+    }
+}
+
+/// Escapes a node path for the manifest, so that it cannot be mistaken for the end of a field.
+fn escape(path: &str) -> Cow<'_, str> {
+    if !path.contains(['\\', '\t', '\n', '\r']) {
+        return Cow::Borrowed(path)
+    }
+    let mut escaped = String::with_capacity(path.len() + 1);
+    for c in path.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Options for extracting the bitmaps and audio below a node.
+///
+/// The output directory mirrors the node tree from the root, so the bitmap
+/// `Mob/100100.img/stand/0` is saved as `Mob/100100.img/stand/0.png` whichever node extraction
+/// starts from. Characters which are not allowed in file names are replaced
+/// with `_`. If that makes the names of siblings clash, ignoring case, every sibling after the
+/// first gets a suffix, so `a?` and `a*` are saved as `a_` and `a_~1`.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    filter: Filter,
+    threads: usize,
+    overwrite: bool,
+}
+
+impl ExtractOptions {
+    /// Creates options which extract both bitmaps and audio using one thread per CPU, skipping
+    /// files which already exist.
+    pub fn new() -> ExtractOptions {
+        ExtractOptions {
+            filter: Filter::All,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            overwrite: false,
+        }
+    }
+    /// Sets which nodes are extracted.
+    pub fn filter(&mut self, filter: Filter) -> &mut ExtractOptions {
+        self.filter = filter;
+        self
+    }
+    /// Sets the number of threads files are written from. Zero is treated as one.
+    pub fn threads(&mut self, threads: usize) -> &mut ExtractOptions {
+        self.threads = threads.max(1);
+        self
+    }
+    /// Sets whether files which already exist are written again instead of skipped.
+    pub fn overwrite(&mut self, overwrite: bool) -> &mut ExtractOptions {
+        self.overwrite = overwrite;
+        self
+    }
+    /// Extracts the descendants of `node` which match the filter into `dir`, and writes the
+    /// manifest to `MANIFEST` in `dir`.
+    ///
+    /// Each file is written under a temporary name and renamed once complete, so an interrupted
+    /// extraction can be resumed without leaving truncated files behind.
+    pub fn extract(&self, node: Node, dir: &Path) -> Result<Manifest, Error> {
+        let jobs = collect(node, self.filter);
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let mut results: Vec<(usize, Result<Status, Error>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(jobs.len())).map(|_| scope.spawn(|| {
+                let mut results = Vec::new();
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let job = match jobs.get(index) {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    let result = self.save(job, dir);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    results.push((index, result));
+                }
+                results
+            })).collect();
+            workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+        });
+        results.sort_by_key(|&(index, _)| index);
+        let statuses = results.into_iter().map(|(_, result)| result)
+            .collect::<Result<Vec<_>, _>>()?;
+        let entries = jobs.into_iter().zip(statuses).map(|(job, status)| Entry {
+            dtype: job.node.dtype(),
+            path: job.path,
+            file: job.file,
+            status,
+        }).collect();
+        let manifest = Manifest { entries };
+        fs::create_dir_all(dir)?;
+        manifest.write(BufWriter::new(FsFile::create(dir.join(MANIFEST))?))?;
+        Ok(manifest)
+    }
+    fn save(&self, job: &Job, dir: &Path) -> Result<Status, Error> {
+        let path = dir.join(&job.file);
+        if !self.overwrite && path.exists() {
+            return Ok(Status::Skipped)
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut partial = path.clone().into_os_string();
+        partial.push(".part");
+        {
+            let mut out = BufWriter::new(FsFile::create(&partial)?);
+            if let Some(bitmap) = job.node.bitmap() {
+                bitmap.to_png(&mut out)?;
+            } else if let Some(audio) = job.node.audio() {
+                write_audio(&mut out, audio)?;
+            }
+            out.flush()?;
+        }
+        fs::rename(&partial, &path)?;
+        Ok(Status::Written)
+    }
+}
+
+impl Default for ExtractOptions {
+    fn default() -> ExtractOptions {
+        ExtractOptions::new()
+    }
+}
+
+struct Job<'a> {
+    node: Node<'a>,
+    path: String,
+    file: PathBuf,
+}
+
+/// Finds the descendants of `node` which match `filter`, along with their node paths and file
+/// paths.
+fn collect(node: Node, filter: Filter) -> Vec<Job> {
+    let prefix = node.path();
+    let base: PathBuf = prefix.split('/').filter(|name| !name.is_empty()).map(sanitize).collect();
+    // The file names of the ancestors of the current node below `node`, and for each of them the
+    // names already taken in the directory they are in, in lower case.
+    let mut names: Vec<String> = Vec::new();
+    let mut taken: Vec<HashSet<String>> = Vec::new();
+    let mut jobs = Vec::new();
+    let mut walk = Walker::new().paths(true).walk(node);
+    while let Some(child) = walk.next() {
+        let depth = walk.depth();
+        names.truncate(depth - 1);
+        taken.truncate(depth);
+        taken.resize_with(depth, HashSet::new);
+        let taken = &mut taken[depth - 1];
+        let extract = filter.matches(child.dtype());
+        let sanitized = sanitize(child.name());
+        let file = |name: &str| format!("{}.{}", name, extension(child)).to_lowercase();
+        let mut name = sanitized.clone();
+        let mut suffix = 0;
+        while taken.contains(&name.to_lowercase()) || extract && taken.contains(&file(&name)) {
+            suffix += 1;
+            name = format!("{}~{}", sanitized, suffix);
+        }
+        taken.insert(name.to_lowercase());
+        if extract {
+            taken.insert(file(&name));
+        }
+        names.push(name);
+        if extract {
+            let mut file = base.clone();
+            file.extend(&names);
+            let mut file = file.into_os_string();
+            file.push(".");
+            file.push(extension(child));
+            let path = match &*prefix {
+                "" => walk.path().to_owned(),
+                prefix => format!("{}/{}", prefix, walk.path()),
+            };
+            jobs.push(Job { node: child, path, file: file.into() });
+        }
+    }
+    jobs
+}
+
+/// Replaces the characters of a node name which are not allowed in file names on some platforms.
+fn sanitize(name: &str) -> String {
+    let name: String = name.chars().map(|c| match c {
+        '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+        c if c.is_control() => '_',
+        c => c,
+    }).collect();
+    match &*name {
+        "" | "." | ".." => format!("_{}", name),
+        _ => name,
+    }
+}
+
+fn extension(node: Node) -> &'static str {
+    match node.audio() {
+        None => "png",
+        Some(audio) => match audio.format() {
+            Format::Mp3 => "mp3",
+            Format::Pcm => "wav",
+            Format::Unknown(_) => match audio.data() {
+                [b'R', b'I', b'F', b'F', ..] => "wav",
+                [b'O', b'g', b'g', b'S', ..] => "ogg",
+                _ => "bin",
+            },
+        },
+    }
+}
+
+/// Writes MP3 and other encoded audio as is, and wraps PCM samples in a WAV file using the
+/// `WAVEFORMATEX` from the wz audio header.
+fn write_audio<W: Write>(mut out: W, audio: Audio) -> io::Result<()> {
+    let data = audio.data();
+    if audio.format() != Format::Pcm {
+        return out.write_all(data)
+    }
+    let format = audio.wave_format();
+    let padding = data.len() % 2;
+    let size = 4 + 8 + format.len() + 8 + data.len() + padding;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(size as u32).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&(format.len() as u32).to_le_bytes())?;
+    out.write_all(format)?;
+    out.write_all(b"data")?;
+    out.write_all(&(data.len() as u32).to_le_bytes())?;
+    out.write_all(data)?;
+    out.write_all(&[0; 1][..padding])
+}
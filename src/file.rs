@@ -2,23 +2,49 @@
 //! Stuff for working with NX files
 
 use memmap::Mmap;
+use std::collections::VecDeque;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter};
 use std::fmt::Error as FmtError;
 use std::fs::File as FsFile;
 use std::io::Error as IoError;
-use std::mem::size_of;
+use std::io::Read;
+use std::mem::{size_of, transmute};
 use std::path::Path;
 use std::result::Result;
-use std::slice::from_raw_parts;
-use std::str::from_utf8_unchecked;
+use std::slice::{from_raw_parts, from_raw_parts_mut};
+use std::str::{from_utf8, from_utf8_unchecked};
+use std::sync::OnceLock;
 
 use repr::{self, Header};
 
-pub use node::{Node};
+pub use node::{Node, NodeId};
 pub use node::{GenericNode};
 pub use node::{Type};
 
+/// One of the tables referenced by the file header.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Table {
+    /// The node table.
+    Node,
+    /// The string offset table.
+    String,
+    /// The bitmap offset table.
+    Bitmap,
+    /// The audio offset table.
+    Audio,
+}
+impl Display for Table {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        fmt.write_str(match *self {
+            Table::Node => "node",
+            Table::String => "string",
+            Table::Bitmap => "bitmap",
+            Table::Audio => "audio",
+        })
+    }
+}
+
 /// An error occuring anywhere in the library.
 #[derive(Debug)]
 pub enum Error {
@@ -28,27 +54,266 @@ pub enum Error {
     InvalidMagic,
     /// File was too short.
     TooShort,
+    /// A table does not fit inside the file.
+    TableOutOfBounds {
+        /// The table which does not fit.
+        table: Table,
+        /// The offset of the table from the start of the file.
+        offset: u64,
+        /// The length of the file in bytes.
+        len: u64,
+    },
+    /// The node table is empty, so there is no root node.
+    NoRoot,
+    /// The children of a node extend past the end of the node table.
+    ChildrenOutOfBounds {
+        /// The index of the node.
+        node: u32,
+        /// The index of the first child.
+        children: u32,
+        /// The number of children.
+        count: u16,
+    },
+    /// A node refers to a string which is not in the string table.
+    BadStringIndex {
+        /// The index of the node.
+        node: u32,
+        /// The string index stored in the node.
+        index: u32,
+        /// The number of strings in the string table.
+        count: u32,
+    },
+    /// A node refers to a bitmap which is not in the bitmap table.
+    BadBitmapIndex {
+        /// The index of the node.
+        node: u32,
+        /// The bitmap index stored in the node.
+        index: u32,
+        /// The number of bitmaps in the bitmap table.
+        count: u32,
+    },
+    /// A node refers to audio which is not in the audio table.
+    BadAudioIndex {
+        /// The index of the node.
+        node: u32,
+        /// The audio index stored in the node.
+        index: u32,
+        /// The number of audio in the audio table.
+        count: u32,
+    },
+    /// The data of a string extends past the end of the file.
+    StringOutOfBounds {
+        /// The index of the string.
+        index: u32,
+        /// The offset of the string from the start of the file.
+        offset: u64,
+    },
+    /// The data of a bitmap extends past the end of the file.
+    BitmapOutOfBounds {
+        /// The index of the bitmap.
+        index: u32,
+        /// The offset of the bitmap from the start of the file.
+        offset: u64,
+    },
+    /// The data of an audio node extends past the end of the file.
+    AudioOutOfBounds {
+        /// The index of the node.
+        node: u32,
+        /// The offset of the audio from the start of the file.
+        offset: u64,
+        /// The length of the audio in bytes.
+        length: u32,
+    },
+    /// A string is not valid UTF-8.
+    InvalidUtf8 {
+        /// The index of the string.
+        index: u32,
+    },
+    /// A node has a type which is not known.
+    UnknownType {
+        /// The index of the node.
+        node: u32,
+        /// The raw type stored in the node.
+        dtype: u16,
+    },
+    /// A path could not be resolved because one of its segments does not exist.
+    NotFound {
+        /// The path which was being resolved.
+        path: String,
+        /// The first segment of the path which could not be found.
+        segment: String,
+    },
+    /// A link points to a node which does not exist.
+    DanglingLink {
+        /// The path of the node containing the link.
+        path: String,
+        /// The path the link points to.
+        target: String,
+    },
+    /// Following links leads back to a node which was already visited.
+    LinkCycle {
+        /// The path of the node where the cycle was found.
+        path: String,
+    },
+    /// A node being written has more children than fit in a node.
+    TooManyChildren {
+        /// The path of the node.
+        path: String,
+        /// The number of children.
+        count: usize,
+    },
+    /// An unknown value being written has the type of a value this library knows.
+    KnownType {
+        /// The path of the node.
+        path: String,
+        /// The type of the value.
+        dtype: u16,
+    },
+    /// A string being written is longer than fits in the string table.
+    StringTooLong {
+        /// The length of the string in bytes.
+        length: usize,
+    },
+    /// Audio being written is longer than the 4 GiB a node can hold.
+    AudioTooLong {
+        /// The path of the node.
+        path: String,
+        /// The length of the audio in bytes.
+        length: usize,
+    },
+    /// A WZ archive could not be read.
+    InvalidWz {
+        /// The offset in the archive where the problem was found.
+        offset: u64,
+        /// The version the archive was read as, or `None` if the problem was found before a
+        /// version was tried.
+        version: Option<u16>,
+        /// What was wrong.
+        reason: &'static str,
+    },
+    /// A JSON document could not be imported.
+    InvalidJson {
+        /// The path of the node where the problem was found, which is empty if the document could
+        /// not be parsed.
+        path: String,
+        /// What was wrong.
+        reason: String,
+    },
+    /// An XML document could not be imported.
+    InvalidXml {
+        /// The path of the node where the problem was found.
+        path: String,
+        /// What was wrong.
+        reason: String,
+    },
+    /// A node being exported to XML has a name or string containing a character which XML 1.0
+    /// cannot represent.
+    XmlCharacter {
+        /// The path of the node.
+        path: String,
+        /// The character.
+        character: char,
+    },
+    /// A node could not be deserialized into the requested type.
+    Deserialize {
+        /// The path of the node which could not be deserialized, or `None` if the error did not
+        /// come from a node.
+        path: Option<String>,
+        /// What was wrong.
+        reason: String,
+    },
+    /// A value could not be serialized into a node.
+    Serialize {
+        /// The path of the node which the value was serialized into, or `None` if the error did
+        /// not come from a node.
+        path: Option<String>,
+        /// What was wrong.
+        reason: String,
+    },
+    /// A WZ canvas is stored in a pixel format which cannot be decoded.
+    UnsupportedCanvas {
+        /// The pixel format.
+        format: u32,
+        /// The scale the image is stored at.
+        scale: u8,
+    },
 }
 impl StdError for Error {
-    fn description(&self) -> &str {
-        match self {
-            &Error::Io(ref e) => e.description(),
-            &Error::InvalidMagic => "Header magic value was invalid",
-            &Error::TooShort => "File was too short for header",
-        }
-    }
-    fn cause(&self) -> Option<&StdError> {
-        match self {
-            &Error::Io(ref e) => Some(e),
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
             _ => None,
         }
     }
 }
 impl Display for Error {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
-        match self.cause() {
-            Some(cause) => write!(fmt, "{} ({})", self.description(), cause),
-            None => write!(fmt, "{}", self.description()),
+        match *self {
+            Error::Io(ref e) => write!(fmt, "I/O error ({})", e),
+            Error::InvalidMagic => write!(fmt, "Header magic value was invalid"),
+            Error::TooShort => write!(fmt, "File was too short for header"),
+            Error::TableOutOfBounds { table, offset, len } => write!(fmt,
+                "The {} table at offset {:#x} does not fit in the file of {} bytes",
+                table, offset, len),
+            Error::NoRoot => write!(fmt, "The node table has no root node"),
+            Error::ChildrenOutOfBounds { node, children, count } => write!(fmt,
+                "Node {} has children {}..{} outside of the node table",
+                node, children, children as u64 + count as u64),
+            Error::BadStringIndex { node, index, count } => write!(fmt,
+                "Node {} refers to string {} but there are only {} strings", node, index, count),
+            Error::BadBitmapIndex { node, index, count } => write!(fmt,
+                "Node {} refers to bitmap {} but there are only {} bitmaps", node, index, count),
+            Error::BadAudioIndex { node, index, count } => write!(fmt,
+                "Node {} refers to audio {} but there are only {} audio", node, index, count),
+            Error::StringOutOfBounds { index, offset } => write!(fmt,
+                "String {} at offset {:#x} runs past the end of the file", index, offset),
+            Error::BitmapOutOfBounds { index, offset } => write!(fmt,
+                "Bitmap {} at offset {:#x} runs past the end of the file", index, offset),
+            Error::AudioOutOfBounds { node, offset, length } => write!(fmt,
+                "Audio of node {} at offset {:#x} with length {} runs past the end of the file",
+                node, offset, length),
+            Error::InvalidUtf8 { index } => write!(fmt, "String {} is not valid UTF-8", index),
+            Error::UnknownType { node, dtype } => write!(fmt,
+                "Node {} has unknown type {}", node, dtype),
+            Error::NotFound { ref path, ref segment } => write!(fmt,
+                "Could not find `{}` while resolving `{}`", segment, path),
+            Error::DanglingLink { ref path, ref target } => write!(fmt,
+                "The link in `{}` points to `{}` which does not exist", path, target),
+            Error::LinkCycle { ref path } => write!(fmt,
+                "Following links leads back to `{}`", path),
+            Error::TooManyChildren { ref path, count } => write!(fmt,
+                "Node `{}` has {} children but at most 65535 can be written", path, count),
+            Error::KnownType { ref path, dtype } => write!(fmt,
+                "Node `{}` has an unknown value of type {}, which is a known type", path, dtype),
+            Error::StringTooLong { length } => write!(fmt,
+                "String of {} bytes is too long to be written", length),
+            Error::AudioTooLong { ref path, length } => write!(fmt,
+                "Node `{}` has {} bytes of audio but at most 4 GiB can be written", path, length),
+            Error::InvalidWz { offset, version: Some(version), reason } => write!(fmt,
+                "WZ archive read as version {} is invalid at offset {:#x}: {}",
+                version, offset, reason),
+            Error::InvalidWz { offset, version: None, reason } => write!(fmt,
+                "WZ archive is invalid at offset {:#x}: {}", offset, reason),
+            Error::InvalidJson { ref path, ref reason } if path.is_empty() => write!(fmt,
+                "Invalid JSON: {}", reason),
+            Error::InvalidJson { ref path, ref reason } => write!(fmt,
+                "Invalid JSON at `{}`: {}", path, reason),
+            Error::InvalidXml { ref path, ref reason } if path.is_empty() => write!(fmt,
+                "Invalid XML: {}", reason),
+            Error::InvalidXml { ref path, ref reason } => write!(fmt,
+                "Invalid XML at `{}`: {}", path, reason),
+            Error::XmlCharacter { ref path, character } => write!(fmt,
+                "Node `{}` contains {:?}, which XML 1.0 cannot represent", path, character),
+            Error::Deserialize { path: Some(ref path), ref reason } => write!(fmt,
+                "Could not deserialize `{}`: {}", path, reason),
+            Error::Deserialize { path: None, ref reason } => write!(fmt,
+                "Could not deserialize: {}", reason),
+            Error::Serialize { path: Some(ref path), ref reason } => write!(fmt,
+                "Could not serialize `{}`: {}", path, reason),
+            Error::Serialize { path: None, ref reason } => write!(fmt,
+                "Could not serialize: {}", reason),
+            Error::UnsupportedCanvas { format, scale } => write!(fmt,
+                "Canvas format {} at scale {} is not supported", format, scale),
         }
     }
 }
@@ -58,48 +323,334 @@ impl From<IoError> for Error {
     }
 }
 
-/// A memory-mapped NX file.
+/// The counts and offsets of the tables in an NX file, as stored in its header.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct HeaderInfo {
+    /// Number of nodes in the node table.
+    pub node_count: u32,
+    /// Offset of the node table from the start of the file.
+    pub node_offset: u64,
+    /// Number of strings in the string table.
+    pub string_count: u32,
+    /// Offset of the string offset table from the start of the file.
+    pub string_offset: u64,
+    /// Number of bitmaps in the bitmap table.
+    pub bitmap_count: u32,
+    /// Offset of the bitmap offset table from the start of the file.
+    pub bitmap_offset: u64,
+    /// Number of audio in the audio table.
+    pub audio_count: u32,
+    /// Offset of the audio offset table from the start of the file.
+    pub audio_offset: u64,
+}
+
+/// How the contents of an NX file are loaded by `OpenOptions`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Strategy {
+    /// Memory-map the file. This is the fastest way to open a file, but if the file is
+    /// truncated or replaced while it is mapped, reading from it may kill the process.
+    Mmap,
+    /// Read the whole file into an aligned buffer on the heap. Changes made to the file on disk
+    /// after it is opened have no effect on the loaded data.
+    Read,
+}
+
+/// How thoroughly an NX file is checked by `OpenOptions`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Validation {
+    /// Check every table, node, string, bitmap and audio reference in the file.
+    Full,
+    /// Only check the length of the file and the magic bytes in the header.
+    Header,
+}
+
+/// Options for opening an NX file.
+///
+/// By default files are memory-mapped and fully validated, which is the same as `File::open`.
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    strategy: Strategy,
+    validation: Validation,
+    unknown_types: bool,
+}
+
+impl OpenOptions {
+    /// Creates the default options.
+    pub fn new() -> OpenOptions {
+        OpenOptions {
+            strategy: Strategy::Mmap,
+            validation: Validation::Full,
+            unknown_types: false,
+        }
+    }
+    /// Sets how the contents of the file are loaded.
+    pub fn strategy(&mut self, strategy: Strategy) -> &mut OpenOptions {
+        self.strategy = strategy;
+        self
+    }
+    /// Sets how thoroughly the file is checked when it is opened.
+    ///
+    /// # Safety
+    ///
+    /// Anything less than `Validation::Full` assumes the NX file is correct and UB may occur if
+    /// there are mistakes.
+    pub unsafe fn validation(&mut self, validation: Validation) -> &mut OpenOptions {
+        self.validation = validation;
+        self
+    }
+    /// Sets whether full validation accepts nodes with types this library does not know, which
+    /// are read as `Type::Unknown`. Their data is never interpreted, so this is safe. By default
+    /// such files are rejected with `Error::UnknownType`.
+    pub fn allow_unknown_types(&mut self, allow: bool) -> &mut OpenOptions {
+        self.unknown_types = allow;
+        self
+    }
+    /// Opens the NX file at `path` with these options.
+    pub fn open(&self, path: &Path) -> Result<File, Error> {
+        let mut fs = FsFile::open(path)?;
+        let backing: Backing = match self.strategy {
+            Strategy::Mmap => Box::new(unsafe { Mmap::map(&fs)? }),
+            Strategy::Read => Box::new(AlignedBuf::read(&mut fs)?),
+        };
+        self.load(backing)
+    }
+    /// Creates an NX file from bytes in memory with these options, like `File::from_bytes`. The
+    /// strategy does not apply, as the bytes are already loaded.
+    pub fn open_bytes<T>(&self, bytes: T) -> Result<File, Error>
+        where T: AsRef<[u8]> + Send + Sync + 'static {
+        self.load(Box::new(bytes))
+    }
+    /// Sets up a file over the loaded bytes and validates it as these options say.
+    fn load(&self, backing: Backing) -> Result<File, Error> {
+        let file = unsafe { File::from_backing(backing)? };
+        if self.validation == Validation::Full {
+            file.validate(self.unknown_types)?;
+        }
+        Ok(file)
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> OpenOptions {
+        OpenOptions::new()
+    }
+}
+
+/// A heap buffer holding a whole file, aligned so that the tables can be read in place.
+struct AlignedBuf {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl AlignedBuf {
+    /// Reads the rest of `fs` into a new buffer.
+    fn read(fs: &mut FsFile) -> Result<AlignedBuf, IoError> {
+        let len = fs.metadata()?.len() as usize;
+        let mut buf = AlignedBuf {
+            words: vec![0; len.div_ceil(size_of::<u64>())],
+            len,
+        };
+        fs.read_exact(buf.bytes_mut())?;
+        Ok(buf)
+    }
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { from_raw_parts_mut(self.words.as_mut_ptr() as *mut u8, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuf {
+    fn as_ref(&self) -> &[u8] {
+        unsafe { from_raw_parts(self.words.as_ptr() as *const u8, self.len) }
+    }
+}
+
+/// Marks a node without a parent in the parent index.
+const NO_PARENT: u32 = !0;
+
+/// The bytes an NX file is read from.
+type Backing = Box<dyn AsRef<[u8]> + Send + Sync>;
+
+/// An NX file, either memory-mapped or backed by bytes in memory.
 pub struct File {
     #[allow(dead_code)]
-    map: Mmap,
+    backing: Backing,
+    len: usize,
     data: *const u8,
     header: *const Header,
     nodetable: *const repr::Node,
     stringtable: *const u64,
     audiotable: *const u64,
     bitmaptable: *const u64,
+    parents: OnceLock<Vec<u32>>,
 }
 
 impl File {
-    /// Opens an NX file via memory-mapping. This also checks the magic bytes in the header.
+    /// Opens an NX file via memory-mapping.
     ///
-    /// This is unsafe because it assumes the NX file is correct and UB may occur if
-    /// there are mistakes.
-    pub unsafe fn open(path: &Path) -> Result<File, Error> {
-        let file = try!(FsFile::open(path));
-        let map = try!(Mmap::map(&file));
-        if map.len() < size_of::<Header>() {
+    /// Every table, node, string, bitmap and audio reference in the file is checked once here.
+    /// The checks only hold for as long as the file on disk stays the same. The file remains
+    /// mapped while it is open, so if it is modified or truncated in the meantime, reading from
+    /// it may cause UB or kill the process.
+    ///
+    /// Use `OpenOptions` with `Strategy::Read` to read the file into memory instead, which is
+    /// not affected by later changes to the file.
+    pub fn open(path: &Path) -> Result<File, Error> {
+        OpenOptions::new().open(path)
+    }
+    /// Opens an NX file via memory-mapping. This only checks the magic bytes in the header.
+    ///
+    /// # Safety
+    ///
+    /// This assumes the NX file is correct and UB may occur if there are mistakes.
+    pub unsafe fn open_unchecked(path: &Path) -> Result<File, Error> {
+        OpenOptions::new().validation(Validation::Header).open(path)
+    }
+    /// Creates an NX file from bytes in memory, such as a `Vec<u8>`, `Box<[u8]>`, `Arc<[u8]>`
+    /// or a `&'static [u8]` from `include_bytes!`.
+    ///
+    /// The file is checked in the same way as `File::open`. Use `OpenOptions::open_bytes` to
+    /// accept nodes of unknown types.
+    pub fn from_bytes<T>(bytes: T) -> Result<File, Error>
+        where T: AsRef<[u8]> + Send + Sync + 'static {
+        OpenOptions::new().open_bytes(bytes)
+    }
+    /// Creates an NX file from bytes in memory. This only checks the magic bytes in the header.
+    ///
+    /// # Safety
+    ///
+    /// This assumes the NX file is correct and UB may occur if there are mistakes.
+    pub unsafe fn from_bytes_unchecked<T>(bytes: T) -> Result<File, Error>
+        where T: AsRef<[u8]> + Send + Sync + 'static {
+        File::from_backing(Box::new(bytes))
+    }
+    /// Sets up the table pointers over the backing bytes. The bytes are only borrowed once,
+    /// after they have been boxed, so the pointers stay valid for as long as the box lives.
+    unsafe fn from_backing(backing: Backing) -> Result<File, Error> {
+        let (data, len) = {
+            let bytes = (*backing).as_ref();
+            (bytes.as_ptr(), bytes.len())
+        };
+        if len < size_of::<Header>() {
             return Err(Error::TooShort)
         }
-        let data = map.as_ptr();
         let header = data as *const Header;
         if (*header).magic != 0x34474B50 {
             return Err(Error::InvalidMagic)
         }
-        let nodetable = data.offset((*header).nodeoffset as isize) as *const repr::Node;
-        let stringtable = data.offset((*header).stringoffset as isize) as *const u64;
-        let audiotable = data.offset((*header).audiooffset as isize) as *const u64;
-        let bitmaptable = data.offset((*header).bitmapoffset as isize) as *const u64;
+        let nodetable = data.wrapping_offset((*header).nodeoffset as isize) as *const repr::Node;
+        let stringtable = data.wrapping_offset((*header).stringoffset as isize) as *const u64;
+        let audiotable = data.wrapping_offset((*header).audiooffset as isize) as *const u64;
+        let bitmaptable = data.wrapping_offset((*header).bitmapoffset as isize) as *const u64;
         Ok(File {
-            map: map,
-            data: data,
-            header: header,
-            nodetable: nodetable,
-            stringtable: stringtable,
-            audiotable: audiotable,
-            bitmaptable: bitmaptable,
+            backing,
+            len,
+            data,
+            header,
+            nodetable,
+            stringtable,
+            audiotable,
+            bitmaptable,
+            parents: OnceLock::new(),
         })
     }
+    /// Checks that everything the file refers to lies within the file, and that every node has
+    /// a known type unless `unknown_types` is set.
+    fn validate(&self, unknown_types: bool) -> Result<(), Error> {
+        let len = self.len as u64;
+        let header = self.header();
+        let fits = |offset: u64, size: u64| offset.checked_add(size).is_some_and(|end| end <= len);
+        let table = |table: Table, offset: u64, count: u32, size: usize| {
+            match (count as u64).checked_mul(size as u64) {
+                Some(size) if fits(offset, size) => Ok(()),
+                _ => Err(Error::TableOutOfBounds { table, offset, len }),
+            }
+        };
+        let (nodecount, stringcount) = (header.nodecount, header.stringcount);
+        let (bitmapcount, audiocount) = (header.bitmapcount, header.audiocount);
+        table(Table::Node, header.nodeoffset, nodecount, size_of::<repr::Node>())?;
+        table(Table::String, header.stringoffset, stringcount, size_of::<u64>())?;
+        table(Table::Bitmap, header.bitmapoffset, bitmapcount, size_of::<u64>())?;
+        table(Table::Audio, header.audiooffset, audiocount, size_of::<u64>())?;
+        if nodecount == 0 {
+            return Err(Error::NoRoot)
+        }
+        for index in 0..stringcount {
+            let offset = unsafe { self.stringtable.offset(index as isize).read_unaligned() };
+            if !fits(offset, 2) {
+                return Err(Error::StringOutOfBounds { index, offset })
+            }
+            let ptr = unsafe { self.data.offset(offset as isize) };
+            let size = unsafe { (ptr as *const u16).read_unaligned() };
+            if !fits(offset + 2, size as u64) {
+                return Err(Error::StringOutOfBounds { index, offset })
+            }
+            let bytes = unsafe { from_raw_parts(ptr.offset(2), size as usize) };
+            if from_utf8(bytes).is_err() {
+                return Err(Error::InvalidUtf8 { index })
+            }
+        }
+        for index in 0..bitmapcount {
+            let offset = unsafe { self.bitmaptable.offset(index as isize).read_unaligned() };
+            if !fits(offset, 4) {
+                return Err(Error::BitmapOutOfBounds { index, offset })
+            }
+            let ptr = unsafe { self.data.offset(offset as isize) };
+            let size = unsafe { (ptr as *const u32).read_unaligned() };
+            if !fits(offset + 4, size as u64) {
+                return Err(Error::BitmapOutOfBounds { index, offset })
+            }
+        }
+        for index in 0..nodecount {
+            let node = unsafe { self.get_node(index) };
+            let (children, count) = (node.children, node.count);
+            if count > 0 && children as u64 + count as u64 > nodecount as u64 {
+                return Err(Error::ChildrenOutOfBounds { node: index, children, count })
+            }
+            if node.name >= stringcount {
+                return Err(Error::BadStringIndex {
+                    node: index, index: node.name, count: stringcount,
+                })
+            }
+            match Type::from_raw(node.dtype) {
+                Type::Empty | Type::Integer | Type::Float | Type::Vector => (),
+                Type::String => {
+                    let string = unsafe { transmute::<u64, repr::String>(node.data) };
+                    if string.index >= stringcount {
+                        return Err(Error::BadStringIndex {
+                            node: index, index: string.index, count: stringcount,
+                        })
+                    }
+                },
+                Type::Bitmap => {
+                    let bitmap = unsafe { transmute::<u64, repr::Bitmap>(node.data) };
+                    if bitmap.index >= bitmapcount {
+                        return Err(Error::BadBitmapIndex {
+                            node: index, index: bitmap.index, count: bitmapcount,
+                        })
+                    }
+                },
+                Type::Audio => {
+                    let audio = unsafe { transmute::<u64, repr::Audio>(node.data) };
+                    if audio.index >= audiocount {
+                        return Err(Error::BadAudioIndex {
+                            node: index, index: audio.index, count: audiocount,
+                        })
+                    }
+                    let offset = unsafe {
+                        self.audiotable.offset(audio.index as isize).read_unaligned()
+                    };
+                    if !fits(offset, audio.length as u64) {
+                        return Err(Error::AudioOutOfBounds {
+                            node: index, offset, length: audio.length,
+                        })
+                    }
+                },
+                Type::Unknown(_) if unknown_types => (),
+                Type::Unknown(dtype) => return Err(Error::UnknownType { node: index, dtype }),
+            }
+        }
+        Ok(())
+    }
     /// Gets the file header.
     #[inline]
     fn header(&self) -> &Header {
@@ -110,37 +661,142 @@ impl File {
     pub fn node_count(&self) -> u32 {
         self.header().nodecount
     }
+    /// Number of strings in the file
+    #[inline]
+    pub fn string_count(&self) -> u32 {
+        self.header().stringcount
+    }
+    /// Number of bitmaps in the file
+    #[inline]
+    pub fn bitmap_count(&self) -> u32 {
+        self.header().bitmapcount
+    }
+    /// Number of audio in the file
+    #[inline]
+    pub fn audio_count(&self) -> u32 {
+        self.header().audiocount
+    }
+    /// Gets the counts and offsets of all the tables from the file header.
+    #[inline]
+    pub fn header_info(&self) -> HeaderInfo {
+        let header = self.header();
+        HeaderInfo {
+            node_count: header.nodecount,
+            node_offset: header.nodeoffset,
+            string_count: header.stringcount,
+            string_offset: header.stringoffset,
+            bitmap_count: header.bitmapcount,
+            bitmap_offset: header.bitmapoffset,
+            audio_count: header.audiocount,
+            audio_offset: header.audiooffset,
+        }
+    }
     /// Gets the root node of the file.
     #[inline]
-    pub fn root<'a>(&'a self) -> Node<'a> {
+    pub fn root(&self) -> Node<'_> {
         unsafe { Node::construct(&*self.nodetable, self) }
     }
     /// Gets the string at the specified index in the string table.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than the number of strings in the file.
     #[inline]
     pub unsafe fn get_str(&self, index: u32) -> &str {
-        let off = *self.stringtable.offset(index as isize);
+        let off = self.stringtable.offset(index as isize).read_unaligned();
         let ptr = self.data.offset(off as isize);
-        let size = ptr as *const u16;
-        from_utf8_unchecked(from_raw_parts(ptr.offset(2), (*size) as usize))
+        let size = (ptr as *const u16).read_unaligned();
+        from_utf8_unchecked(from_raw_parts(ptr.offset(2), size as usize))
+    }
+    /// Gets the node with the specified id, or `None` if the id is not in the node table.
+    #[inline]
+    pub fn node(&self, id: NodeId) -> Option<Node<'_>> {
+        if id.0 < self.node_count() {
+            Some(unsafe { Node::construct(self.get_node(id.0), self) })
+        } else {
+            None
+        }
+    }
+    /// Builds the parent index used by `Node::parent` and friends if it has not been built yet.
+    ///
+    /// The index takes one `u32` per node and is otherwise built the first time a node is asked
+    /// for its parent, so this only needs to be called to move that cost somewhere else.
+    pub fn build_parent_index(&self) {
+        self.parents();
+    }
+    /// Gets the parent index, building it if needed. Nodes are given a parent in breadth-first
+    /// order from the root, so every node is given at most one parent and there are no cycles.
+    fn parents(&self) -> &[u32] {
+        self.parents.get_or_init(|| {
+            let count = self.node_count();
+            let mut parents = vec![NO_PARENT; count as usize];
+            let mut queue = VecDeque::new();
+            queue.push_back(0);
+            while let Some(index) = queue.pop_front() {
+                let node = unsafe { self.get_node(index) };
+                for child in node.children..node.children.saturating_add(node.count as u32) {
+                    match parents.get_mut(child as usize) {
+                        Some(parent) if *parent == NO_PARENT && child != 0 => {
+                            *parent = index;
+                            queue.push_back(child);
+                        },
+                        _ => (),
+                    }
+                }
+            }
+            parents
+        })
+    }
+    /// Gets the index of the parent of the node at the specified index.
+    #[inline]
+    pub(crate) fn parent_of(&self, index: u32) -> Option<u32> {
+        match self.parents()[index as usize] {
+            NO_PARENT => None,
+            parent => Some(parent),
+        }
     }
     /// Gets the node data at the specified index in the node table.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than the number of nodes in the file.
     #[inline]
-    pub unsafe fn get_node(&self, index: u32) -> &repr::Node {
+    pub(crate) unsafe fn get_node(&self, index: u32) -> &repr::Node {
         &*self.nodetable.offset(index as isize)
     }
+    /// Gets the index in the node table of node data from this file.
+    #[inline]
+    pub(crate) fn node_index(&self, data: &repr::Node) -> u32 {
+        let offset = data as *const repr::Node as usize - self.nodetable as usize;
+        (offset / size_of::<repr::Node>()) as u32
+    }
+    /// Gets a pointer to the node data at the specified index without dereferencing it.
+    #[inline]
+    pub(crate) fn node_ptr(&self, index: u32) -> *const repr::Node {
+        self.nodetable.wrapping_offset(index as isize)
+    }
     /// Gets the audio data at the specified index in the node table.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than the number of audio in the file and `length` must not run past
+    /// the end of the file.
     #[inline]
     pub unsafe fn get_audio(&self, index: u32, length: u32) -> &[u8] {
-        let off = *self.audiotable.offset(index as isize);
+        let off = self.audiotable.offset(index as isize).read_unaligned();
         let ptr = self.data.offset(off as isize);
         from_raw_parts(ptr, length as usize)
     }
     /// Gets the bitmap data at the specified index in the node table.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than the number of bitmaps in the file.
     #[inline]
     pub unsafe fn get_bitmap(&self, index: u32) -> &[u8] {
-        let off = *self.bitmaptable.offset(index as isize);
+        let off = self.bitmaptable.offset(index as isize).read_unaligned();
         let ptr = self.data.offset(off as isize);
-        let len = *(ptr as *const u32);
+        let len = (ptr as *const u32).read_unaligned();
         from_raw_parts(ptr.offset(4), len as usize)
     }
 }
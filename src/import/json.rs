@@ -0,0 +1,195 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Importing NX files from JSON
+//!
+//! `ImportOptions::read_json` reads the format written by `export::json`:
+//!
+//! * Objects become empty nodes with a child for each key.
+//! * An object with a `"$value"` key becomes a node with that value and a child for each of the
+//!   other keys. Keys starting with `$$` lose their first `$`, which is how children named
+//!   `$value` are kept apart from the value.
+//! * Arrays become empty nodes with children named `0`, `1` and so on.
+//! * Integers, floats and strings become nodes of the same type. A number with a fractional part
+//!   or an exponent is a float, even if it is `1.0`.
+//! * `true` and `false` become the integers `1` and `0`, and `null` becomes an empty node.
+//! * Objects with `"type": "vector"` become vectors of their integer keys `"x"` and `"y"`.
+//! * Objects with `"type": "bitmap"` or `"type": "audio"` become bitmaps or audio, and objects
+//!   with `"type": "unknown"` become nodes of the unknown type in `"dtype"` with the 8 bytes of
+//!   raw data in `"data"` in base64. An object with one of these types and a `"$value"` key is
+//!   a node with children instead.
+//!
+//! Bitmaps and audio are read from `"data"` in base64 if it is present, as BGRA8 pixels for
+//! bitmaps and as the wz audio header followed by the audio data for audio. Otherwise they are
+//! copied byte for byte from the base node, using the path in `"source"` relative to the base
+//! node or, without `"source"`, the path of the node being imported.
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{self, Map, Value as JsonValue};
+use std::convert::TryFrom;
+use std::io::Read;
+
+use export::json::VALUE_KEY;
+use file::Error;
+use import::{copy, ImportOptions};
+use write::{Builder, Handle, NodeValue};
+
+impl<'a> ImportOptions<'a> {
+    /// Reads a JSON document into a builder, which the NX file can then be written from.
+    pub fn read_json<R: Read>(&self, reader: R) -> Result<Builder, Error> {
+        let json = serde_json::from_reader(reader).map_err(parse_error)?;
+        import(self, &json)
+    }
+    /// Reads a JSON document from a string into a builder.
+    pub fn read_json_str(&self, json: &str) -> Result<Builder, Error> {
+        let json = serde_json::from_str(json).map_err(parse_error)?;
+        import(self, &json)
+    }
+}
+
+fn import(options: &ImportOptions, json: &JsonValue) -> Result<Builder, Error> {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    node(options, &mut builder, root, json, &mut String::new())?;
+    Ok(builder)
+}
+
+/// Sets the value of `handle` and adds its children, keeping track of its path for errors and
+/// for finding its source in the base node.
+fn node(options: &ImportOptions, builder: &mut Builder, handle: Handle, json: &JsonValue,
+    path: &mut String) -> Result<(), Error> {
+    let children: Vec<(&str, &JsonValue)> = match *json {
+        JsonValue::Object(ref map) if !is_value(map) => {
+            if let Some(json) = map.get(VALUE_KEY) {
+                let value = value(options, json, path)?;
+                builder.set_value(handle, value);
+            }
+            map.iter().filter(|&(name, _)| name != VALUE_KEY).map(|(name, child)| {
+                (if name.starts_with("$$") { &name[1..] } else { &**name }, child)
+            }).collect()
+        },
+        JsonValue::Array(ref items) => {
+            let names: Vec<String> = (0..items.len()).map(|index| index.to_string()).collect();
+            for (name, item) in names.iter().zip(items) {
+                child(options, builder, handle, name, item, path)?;
+            }
+            return Ok(())
+        },
+        _ => {
+            let value = value(options, json, path)?;
+            builder.set_value(handle, value);
+            return Ok(())
+        },
+    };
+    for (name, json) in children {
+        child(options, builder, handle, name, json, path)?;
+    }
+    Ok(())
+}
+
+fn child(options: &ImportOptions, builder: &mut Builder, parent: Handle, name: &str,
+    json: &JsonValue, path: &mut String) -> Result<(), Error> {
+    let len = path.len();
+    if !path.is_empty() { path.push('/') }
+    path.push_str(name);
+    let handle = builder.add(parent, name, NodeValue::Empty);
+    node(options, builder, handle, json, path)?;
+    path.truncate(len);
+    Ok(())
+}
+
+/// Converts a JSON value which is not a container of children into a node value.
+fn value(options: &ImportOptions, json: &JsonValue, path: &str) -> Result<NodeValue, Error> {
+    Ok(match *json {
+        JsonValue::Null => NodeValue::Empty,
+        JsonValue::Bool(value) => NodeValue::Integer(value as i64),
+        JsonValue::Number(ref number) => match number.as_i64() {
+            Some(value) => NodeValue::Integer(value),
+            None if number.is_u64() => return Err(invalid(path, "integer is too large")),
+            None => NodeValue::Float(number.as_f64().unwrap_or(0.0)),
+        },
+        JsonValue::String(ref value) => NodeValue::String(value.clone()),
+        JsonValue::Object(ref map) if is_value(map) => {
+            match map.get("type").and_then(JsonValue::as_str) {
+                Some("vector") => NodeValue::Vector(
+                    integer(map, "x", path)?, integer(map, "y", path)?),
+                Some("unknown") => unknown(map, path)?,
+                _ => media(options, map, path)?,
+            }
+        },
+        _ => return Err(invalid(path, "`$value` must not be an object or array of nodes")),
+    })
+}
+
+/// Converts a bitmap or audio object into a node value.
+fn media(options: &ImportOptions, map: &Map<String, JsonValue>, path: &str)
+    -> Result<NodeValue, Error> {
+    let bitmap = map.get("type").and_then(JsonValue::as_str) == Some("bitmap");
+    let size = if bitmap {
+        Some((integer(map, "width", path)?, integer(map, "height", path)?))
+    } else {
+        None
+    };
+    if let Some(data) = map.get("data") {
+        let data = data.as_str().and_then(|data| STANDARD.decode(data).ok())
+            .ok_or_else(|| invalid(path, "`data` is not a base64 string"))?;
+        return Ok(match size {
+            Some((width, height)) => {
+                if data.len() != width as usize * height as usize * 4 {
+                    return Err(invalid(path, "`data` does not match the bitmap size"))
+                }
+                NodeValue::bitmap(&data, width, height, options.compression)
+            },
+            None => NodeValue::Audio(data),
+        })
+    }
+    let source = match map.get("source") {
+        Some(source) => source.as_str().ok_or_else(|| invalid(path, "`source` is not a string"))?,
+        None => path,
+    };
+    copy(options.base, source, size, |reason| invalid(path, reason))
+}
+
+/// Reads a JSON document into a builder with default options.
+pub fn from_reader<R: Read>(reader: R) -> Result<Builder, Error> {
+    ImportOptions::new().read_json(reader)
+}
+
+/// Reads a JSON document from a string into a builder with default options.
+pub fn from_str(json: &str) -> Result<Builder, Error> {
+    ImportOptions::new().read_json_str(json)
+}
+
+/// Whether an object is a single value rather than a node with children.
+fn is_value(map: &Map<String, JsonValue>) -> bool {
+    match map.get("type").and_then(JsonValue::as_str) {
+        Some("vector") | Some("bitmap") | Some("audio") | Some("unknown") => {
+            !map.contains_key(VALUE_KEY)
+        },
+        _ => false,
+    }
+}
+
+/// Converts an object for a node of an unknown type into a node value.
+fn unknown(map: &Map<String, JsonValue>, path: &str) -> Result<NodeValue, Error> {
+    let data = map.get("data").and_then(JsonValue::as_str)
+        .and_then(|data| STANDARD.decode(data).ok())
+        .and_then(|data| <[u8; 8]>::try_from(&*data).ok())
+        .ok_or_else(|| invalid(path, "`data` is not 8 bytes in base64"))?;
+    Ok(NodeValue::Unknown(integer(map, "dtype", path)?, data))
+}
+
+fn integer<T: TryFrom<i64>>(map: &Map<String, JsonValue>, key: &str, path: &str)
+    -> Result<T, Error> {
+    map.get(key).and_then(JsonValue::as_i64).and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| Error::InvalidJson {
+            path: path.to_owned(),
+            reason: format!("`{}` is missing or out of range", key),
+        })
+}
+
+fn invalid(path: &str, reason: &str) -> Error {
+    Error::InvalidJson { path: path.to_owned(), reason: reason.to_owned() }
+}
+
+fn parse_error(err: serde_json::Error) -> Error {
+    Error::InvalidJson { path: String::new(), reason: err.to_string() }
+}
@@ -0,0 +1,66 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Importing NX files from other formats
+//!
+//! Every format is read into a `Builder` with the same `ImportOptions`. The builder sorts
+//! children by the bytes of their names when the file is written, which is the order `Node::get`
+//! depends on, so documents may list children in any order.
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "xml")]
+pub mod xml;
+
+use bitmap::Compression;
+use file::Error;
+use node::{GenericNode, Node};
+use write::NodeValue;
+
+/// Options for importing a document, used by the `read_*` methods each format adds.
+#[derive(Clone, Copy, Default)]
+pub struct ImportOptions<'a> {
+    base: Option<Node<'a>>,
+    compression: Compression,
+}
+
+impl<'a> ImportOptions<'a> {
+    /// Creates options with no base node, so every bitmap and audio must include its data.
+    pub fn new() -> ImportOptions<'a> {
+        ImportOptions::default()
+    }
+    /// Sets the node which bitmaps and audio without data are copied from, which is usually the
+    /// node the document was exported from. Each is copied from the node at the same path below
+    /// the base node, unless a JSON document names another path in `"source"`.
+    pub fn base(&mut self, base: Node<'a>) -> &mut ImportOptions<'a> {
+        self.base = Some(base);
+        self
+    }
+    /// Sets how bitmaps with data are compressed. Bitmaps copied from the base node are not
+    /// compressed again.
+    pub fn compression(&mut self, compression: Compression) -> &mut ImportOptions<'a> {
+        self.compression = compression;
+        self
+    }
+}
+
+/// Copies the bitmap or audio at `source` relative to `base` byte for byte. `size` is the size
+/// the bitmap is expected to have, or `None` if audio is expected. `invalid` creates the error
+/// for a problem which is not a missing node.
+fn copy<F>(base: Option<Node>, source: &str, size: Option<(u16, u16)>, invalid: F)
+    -> Result<NodeValue, Error> where F: Fn(&str) -> Error {
+    let base = base.ok_or_else(|| invalid("there is no data and no base node to copy it from"))?;
+    let node = base.try_resolve(source)?;
+    match size {
+        Some((width, height)) => {
+            let bitmap = node.bitmap()
+                .ok_or_else(|| invalid("the source in the base node is not a bitmap"))?;
+            if (bitmap.width(), bitmap.height()) != (width, height) {
+                return Err(invalid("the size does not match the source bitmap"))
+            }
+            Ok(NodeValue::Bitmap { width, height, data: bitmap.raw().to_vec() })
+        },
+        None => {
+            let audio = node.audio()
+                .ok_or_else(|| invalid("the source in the base node is not audio"))?;
+            Ok(NodeValue::Audio(audio.raw().to_vec()))
+        },
+    }
+}
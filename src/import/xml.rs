@@ -0,0 +1,211 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Importing NX files from the WZ-XML dialect used by HaRepacker and WzDumper
+//!
+//! `ImportOptions::read_xml` reads the format written by `export::xml`, including `<unknown>`,
+//! as well as the `<short>`, `<uol>`, `<null>` and `<extended>` elements written by other tools.
+//! Short, int and long elements become integers, float and double elements become floats, and
+//! UOLs become strings, which `Node::follow` only follows if they start with `../`. The `name`
+//! attribute of the root element is ignored unless the document is read into an existing builder
+//! with `ImportOptions::read_xml_into`.
+//!
+//! Canvases are decoded from the PNG in `basedata` and sounds are read from `basehead` and
+//! `basedata` if they are present. Otherwise they are copied byte for byte from the base node.
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::convert::TryFrom;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use bitmap::Bitmap;
+use file::Error;
+use import::{copy, ImportOptions};
+use write::{Builder, Handle, NodeValue};
+
+impl<'a> ImportOptions<'a> {
+    /// Reads an XML document into a builder, with the root element as the root node.
+    pub fn read_xml<R: BufRead>(&self, reader: R) -> Result<Builder, Error> {
+        let mut builder = Builder::new();
+        import(self, reader, &mut builder, None)?;
+        Ok(builder)
+    }
+    /// Reads an XML document from a string into a builder, with the root element as the root
+    /// node.
+    pub fn read_xml_str(&self, xml: &str) -> Result<Builder, Error> {
+        self.read_xml(xml.as_bytes())
+    }
+    /// Reads an XML document into an existing builder, adding the root element as a child of
+    /// `parent`. This is how a directory of `.img.xml` files can be combined into one NX file.
+    pub fn read_xml_into<R: BufRead>(&self, reader: R, builder: &mut Builder, parent: Handle)
+        -> Result<Handle, Error> {
+        import(self, reader, builder, Some(parent))
+    }
+}
+
+/// Reads the elements of a document, adding the root element to `parent`, or making it the root
+/// node if there is no parent.
+fn import<R: BufRead>(options: &ImportOptions, reader: R, builder: &mut Builder,
+    parent: Option<Handle>) -> Result<Handle, Error> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut path = String::new();
+    // The open elements, with the length of the path before each was entered.
+    let mut stack: Vec<(Handle, usize)> = Vec::new();
+    let mut root = None;
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|err| Error::InvalidXml {
+            path: path.clone(),
+            reason: format!("{} at byte {}", err, reader.error_position()),
+        })?;
+        let (element, empty) = match event {
+            Event::Start(ref element) => (element, false),
+            Event::Empty(ref element) => (element, true),
+            Event::End(_) => {
+                let (handle, len) = stack.pop().expect("end tags are matched by the reader");
+                path.truncate(len);
+                if stack.is_empty() {
+                    root = Some(handle);
+                }
+                buf.clear();
+                continue
+            },
+            Event::Eof => break,
+            _ => {
+                buf.clear();
+                continue
+            },
+        };
+        let attributes = Attributes::new(element, &path)?;
+        let name = attributes.get("name").unwrap_or("").to_owned();
+        let len = path.len();
+        let handle = match stack.last() {
+            Some(&(parent, _)) => {
+                if !path.is_empty() { path.push('/') }
+                path.push_str(&name);
+                builder.add(parent, name, NodeValue::Empty)
+            },
+            None if root.is_some() => return Err(Error::InvalidXml {
+                path: String::new(),
+                reason: "there is more than one root element".to_owned(),
+            }),
+            None => match parent {
+                Some(parent) => builder.add(parent, name, NodeValue::Empty),
+                None => builder.root(),
+            },
+        };
+        let tag = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+        let value = value(options, &tag, &attributes, &path)?;
+        builder.set_value(handle, value);
+        if empty {
+            path.truncate(len);
+            if stack.is_empty() {
+                root = Some(handle);
+            }
+        } else {
+            stack.push((handle, len));
+        }
+        buf.clear();
+    }
+    root.ok_or_else(|| Error::InvalidXml {
+        path: String::new(),
+        reason: "there is no root element".to_owned(),
+    })
+}
+
+/// Converts an element into a node value, copying canvases and sounds without data from the
+/// base node.
+fn value(options: &ImportOptions, tag: &str, attributes: &Attributes, path: &str)
+    -> Result<NodeValue, Error> {
+    Ok(match tag {
+        "imgdir" | "null" | "extended" | "convex" => NodeValue::Empty,
+        "short" | "int" | "long" => NodeValue::Integer(attributes.parse("value", path)?),
+        "float" => NodeValue::Float(attributes.parse::<f32>("value", path)? as f64),
+        "double" => NodeValue::Float(attributes.parse("value", path)?),
+        "string" | "uol" => NodeValue::String(attributes.require("value", path)?.to_owned()),
+        "vector" => NodeValue::Vector(attributes.parse("x", path)?, attributes.parse("y", path)?),
+        "canvas" => {
+            let size = (attributes.parse("width", path)?, attributes.parse("height", path)?);
+            if attributes.get("basedata").is_none() {
+                return copy(options.base, path, Some(size), |reason| invalid(path, reason))
+            }
+            let png = attributes.base64("basedata", path)?;
+            let (pixels, width, height) = Bitmap::from_png(&*png)?;
+            if (width, height) != size {
+                return Err(invalid(path, "the size does not match `basedata`"))
+            }
+            NodeValue::bitmap(&pixels, width, height, options.compression)
+        },
+        "sound" => {
+            if attributes.get("basehead").is_none() || attributes.get("basedata").is_none() {
+                return copy(options.base, path, None, |reason| invalid(path, reason))
+            }
+            let mut audio = attributes.base64("basehead", path)?;
+            audio.extend(attributes.base64("basedata", path)?);
+            NodeValue::Audio(audio)
+        },
+        "unknown" => {
+            let data = attributes.base64("data", path)?;
+            let data = <[u8; 8]>::try_from(&*data)
+                .map_err(|_| invalid(path, "`data` is not 8 bytes"))?;
+            NodeValue::Unknown(attributes.parse("type", path)?, data)
+        },
+        _ => return Err(Error::InvalidXml {
+            path: path.to_owned(),
+            reason: format!("`<{}>` is not a known element", tag),
+        }),
+    })
+}
+
+/// Reads an XML document into a builder with default options.
+pub fn from_reader<R: BufRead>(reader: R) -> Result<Builder, Error> {
+    ImportOptions::new().read_xml(reader)
+}
+
+/// Reads an XML document from a string into a builder with default options.
+pub fn from_str(xml: &str) -> Result<Builder, Error> {
+    ImportOptions::new().read_xml_str(xml)
+}
+
+/// The unescaped attributes of an element. Errors name the path of the element's node.
+struct Attributes {
+    values: Vec<(String, String)>,
+}
+
+impl Attributes {
+    fn new(element: &BytesStart, path: &str) -> Result<Attributes, Error> {
+        let values = element.attributes().map(|attribute| {
+            let attribute = attribute.map_err(|err| invalid(path, &err.to_string()))?;
+            let value = attribute.unescape_value().map_err(|err| invalid(path, &err.to_string()))?;
+            let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+            Ok((key, value.into_owned()))
+        }).collect::<Result<_, Error>>()?;
+        Ok(Attributes { values })
+    }
+    fn get(&self, key: &str) -> Option<&str> {
+        self.values.iter().find(|(k, _)| k == key).map(|(_, value)| &**value)
+    }
+    fn require(&self, key: &str, path: &str) -> Result<&str, Error> {
+        self.get(key).ok_or_else(|| Error::InvalidXml {
+            path: path.to_owned(),
+            reason: format!("`{}` is missing", key),
+        })
+    }
+    fn parse<T: FromStr>(&self, key: &str, path: &str) -> Result<T, Error> {
+        self.require(key, path)?.trim().parse().map_err(|_| Error::InvalidXml {
+            path: path.to_owned(),
+            reason: format!("`{}` is not a valid number", key),
+        })
+    }
+    fn base64(&self, key: &str, path: &str) -> Result<Vec<u8>, Error> {
+        let value = self.require(key, path)?;
+        STANDARD.decode(value.trim()).map_err(|_| Error::InvalidXml {
+            path: path.to_owned(),
+            reason: format!("`{}` is not valid base64", key),
+        })
+    }
+}
+
+fn invalid(path: &str, reason: &str) -> Error {
+    Error::InvalidXml { path: path.to_owned(), reason: reason.to_owned() }
+}
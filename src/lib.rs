@@ -4,14 +4,51 @@
 #![warn(missing_docs)]
 
 extern crate memmap;
+#[cfg(feature = "wz")]
+extern crate aes;
+#[cfg(feature = "wz")]
+extern crate flate2;
+#[cfg(feature = "png")]
+extern crate png;
+#[cfg(any(feature = "json", feature = "xml"))]
+extern crate base64;
+#[cfg(feature = "xml")]
+extern crate quick_xml;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "json")]
+extern crate serde_json;
+#[cfg(feature = "json")]
+extern crate sha2;
 
-pub use file::{Error, File};
-pub use node::{GenericNode, Node, Type};
+pub use arc::ArcNode;
+pub use file::{Error, File, HeaderInfo, OpenOptions};
+pub use node::{GenericNode, Node, NodeId, Type, Value};
 
+pub mod arc;
 pub mod audio;
 pub mod bitmap;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(any(feature = "json", feature = "xml"))]
+pub mod export;
+#[cfg(feature = "extract")]
+pub mod extract;
 pub mod file;
+#[cfg(any(feature = "json", feature = "xml"))]
+pub mod import;
+pub mod link;
 pub mod node;
+#[cfg(feature = "rayon")]
+pub mod par;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod walk;
+pub mod write;
+#[cfg(feature = "wz")]
+pub mod wz;
 mod repr;
 mod lz4;
 
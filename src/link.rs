@@ -0,0 +1,91 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Links between nodes in MapleStory data
+//!
+//! Files converted from WZ often contain nodes which are really references to other nodes. A
+//! canvas may have an `_inlink` child with a path relative to the `.img` node containing it, or an
+//! `_outlink` child with a path from the root of the file. A string node may also be a UOL, which
+//! is a path relative to the parent of the node, such as `../../0/1`.
+//!
+//! NX files have no UOL type, so `wz` and `import::xml` write UOLs as string nodes. Only strings
+//! starting with `../` are recognised as UOLs, so a UOL pointing below the parent of its node,
+//! such as `1` or `face/0`, cannot be told apart from any other string and is not followed.
+
+use bitmap::Bitmap;
+use file::Error;
+use node::{GenericNode, Node};
+
+/// A link from one node to another.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Link<'a> {
+    /// A path relative to the `.img` node containing the node.
+    Inlink(&'a str),
+    /// A path from the root of the file.
+    Outlink(&'a str),
+    /// A path relative to the parent of the node.
+    Uol(&'a str),
+}
+
+impl<'a> Node<'a> {
+    /// Gets the link in this node, if there is one.
+    ///
+    /// An `_outlink` child takes priority over an `_inlink` child. A string node is only treated
+    /// as a UOL if its value starts with `../`, as other strings cannot be told apart from UOLs.
+    /// Use `GenericNode::resolve` on the parent to follow a string known to be a UOL.
+    pub fn link(&self) -> Option<Link<'a>> {
+        if let Some(path) = self.get("_outlink").string() {
+            return Some(Link::Outlink(path))
+        }
+        if let Some(path) = self.get("_inlink").string() {
+            return Some(Link::Inlink(path))
+        }
+        match self.string() {
+            Some(path) if path.starts_with("../") => Some(Link::Uol(path)),
+            _ => None,
+        }
+    }
+    /// Follows links starting from this node until reaching a node which is not a link. If this
+    /// node is not a link, it is returned as is.
+    ///
+    /// This builds the parent index of the file if it has not been built yet.
+    pub fn follow(&self) -> Result<Node<'a>, Error> {
+        let mut visited = vec![self.id()];
+        let mut node = *self;
+        while let Some(link) = node.link() {
+            let (target, path) = match link {
+                Link::Outlink(path) => (node.resolve_outlink(path), path),
+                Link::Inlink(path) => (node.image().resolve(path), path),
+                Link::Uol(path) => (node.parent().resolve(path), path),
+            };
+            node = target.ok_or_else(|| Error::DanglingLink {
+                path: node.path(),
+                target: path.to_owned(),
+            })?;
+            if visited.contains(&node.id()) {
+                return Err(Error::LinkCycle { path: node.path() })
+            }
+            visited.push(node.id());
+        }
+        Ok(node)
+    }
+    /// Gets the bitmap of the node this node links to, or of this node if it is not a link.
+    /// This is `Ok(None)` if that node is not a bitmap node.
+    pub fn bitmap_resolved(&self) -> Result<Option<Bitmap<'a>>, Error> {
+        Ok(self.follow()?.bitmap())
+    }
+    /// Gets the closest `.img` node containing this node, or the root node if there is none.
+    fn image(&self) -> Node<'a> {
+        self.ancestors().find(|node| node.name().ends_with(".img"))
+            .unwrap_or_else(|| self.file().root())
+    }
+    /// Resolves an `_outlink` path from the root of the file. Outlinks start with the name of the
+    /// WZ file they point into, so if the file was converted from that WZ file alone the first
+    /// segment is skipped.
+    fn resolve_outlink(&self, path: &str) -> Option<Node<'a>> {
+        let root = self.file().root();
+        root.resolve(path).or_else(|| {
+            let mut segments = path.splitn(2, '/');
+            segments.next();
+            segments.next().and_then(|rest| root.resolve(rest))
+        })
+    }
+}
@@ -4,7 +4,21 @@ extern "C" {
     fn LZ4_decompress_safe(
         source: *const u8, dest: *mut u8, compressedSize: c_int, maxDecompressedSize: c_int,
     ) -> c_int;
+    fn LZ4_compress_default(
+        source: *const u8, dest: *mut u8, sourceSize: c_int, maxDestSize: c_int,
+    ) -> c_int;
+    fn LZ4_compress_HC(
+        source: *const u8, dest: *mut u8, sourceSize: c_int, maxDestSize: c_int,
+        compressionLevel: c_int,
+    ) -> c_int;
+    fn LZ4_compressBound(inputSize: c_int) -> c_int;
 }
+/// The largest input LZ4 can compress.
+pub const MAX_INPUT_SIZE: usize = 0x7E000000;
+/// The range of LZ4HC compression levels. LZ4HC quietly uses its default level for levels
+/// below the minimum, and the maximum level for levels above it.
+pub const MIN_HC_LEVEL: u8 = 1;
+pub const MAX_HC_LEVEL: u8 = 12;
 pub fn decompress(source: &[u8], dest: &mut [u8]) -> Result<usize, c_int> {
     let ret = unsafe {
         LZ4_decompress_safe(source.as_ptr(), dest.as_mut_ptr(), source.len() as c_int,
@@ -13,3 +27,23 @@ pub fn decompress(source: &[u8], dest: &mut [u8]) -> Result<usize, c_int> {
     if ret < 0 { Err(ret) }
     else { Ok(ret as usize) }
 }
+/// Compresses `source` into a new LZ4 block. A `level` of `None` uses the fast compressor and
+/// `Some` uses LZ4HC at that compression level, which must be from `MIN_HC_LEVEL` to
+/// `MAX_HC_LEVEL`.
+pub fn compress(source: &[u8], level: Option<u8>) -> Vec<u8> {
+    assert!(source.len() <= MAX_INPUT_SIZE);
+    assert!(level.is_none_or(|level| (MIN_HC_LEVEL..=MAX_HC_LEVEL).contains(&level)));
+    let bound = unsafe { LZ4_compressBound(source.len() as c_int) };
+    let mut dest = vec![0; bound as usize];
+    let ret = unsafe {
+        match level {
+            None => LZ4_compress_default(source.as_ptr(), dest.as_mut_ptr(),
+                source.len() as c_int, bound),
+            Some(level) => LZ4_compress_HC(source.as_ptr(), dest.as_mut_ptr(),
+                source.len() as c_int, bound, level as c_int),
+        }
+    };
+    assert!(ret > 0 || source.is_empty());
+    dest.truncate(ret as usize);
+    dest
+}
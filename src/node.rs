@@ -1,13 +1,21 @@
 // Copyright © 2015-2018, Peter Atashian
 //! Stuff for working with NX nodes
 
+use std::borrow::Cow;
 use std::cmp::Ordering::{Equal, Greater, Less};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::iter::once;
 use std::mem::{transmute};
+use std::ptr;
 
 use audio::{Audio};
 use bitmap::{Bitmap};
-use file::{File};
+use file::{Error, File};
 use repr;
+use walk::{Walk, Walker};
+#[cfg(feature = "rayon")]
+use par::{ParDescendants, ParNodes};
 
 pub use repr::Type;
 
@@ -30,6 +38,194 @@ pub trait GenericNode<'a> {
     fn audio(&self) -> Option<Audio<'a>>;
     /// Gets the bitmap value of thise node. This will be `None` if the node is not a bitmap node.
     fn bitmap(&self) -> Option<Bitmap<'a>>;
+    /// Gets the node at the specified path relative to this node. The path is split on `/` and
+    /// each segment is the name of a child, or `..` for the parent. Going up to a parent builds
+    /// the parent index of the file if it has not been built yet.
+    ///
+    /// The default implementation looks up the first segment with `get` and resolves the rest
+    /// from that child, so it cannot go up from this node with a leading `..` or return this
+    /// node for an empty path.
+    fn resolve(&self, path: &str) -> Option<Node<'a>> {
+        self.try_resolve(path).ok()
+    }
+    /// Gets the node at the specified path relative to this node, like `resolve`. If the path
+    /// cannot be resolved, the error names the first segment which could not be found.
+    fn try_resolve(&self, path: &str) -> Result<Node<'a>, Error> {
+        let not_found = |segment: &str| Error::NotFound {
+            path: path.to_owned(),
+            segment: segment.to_owned(),
+        };
+        let mut rest = path;
+        loop {
+            let mut split = rest.splitn(2, '/');
+            let segment = split.next().unwrap_or("");
+            match (segment, split.next()) {
+                ("", Some(tail)) | (".", Some(tail)) => rest = tail,
+                ("", None) | (".", None) | ("..", _) => return Err(not_found(segment)),
+                (_, tail) => {
+                    let node = self.get(segment).ok_or_else(|| not_found(segment))?;
+                    return match tail {
+                        Some(tail) => node.walk(tail).map_err(not_found),
+                        None => Ok(node),
+                    };
+                },
+            }
+        }
+    }
+    /// Gets the value of this node as an integer, converting it if it is stored as another type.
+    /// Floats are truncated towards zero, and strings are parsed as integers or floats after
+    /// trimming whitespace. This will be `None` if there is no such integer.
+    fn as_i64(&self) -> Option<i64> {
+        match self.dtype() {
+            Type::Integer => self.integer(),
+            Type::Float => self.float().and_then(truncate),
+            Type::String => {
+                let string = self.string()?.trim();
+                string.parse().ok().or_else(|| string.parse().ok().and_then(truncate))
+            },
+            _ => None,
+        }
+    }
+    /// Gets the value of this node as an integer like `as_i64`, but `None` if it does not fit in
+    /// an `i32`.
+    fn as_i32(&self) -> Option<i32> {
+        self.as_i64().and_then(|value| i32::try_from(value).ok())
+    }
+    /// Gets the value of this node as an integer like `as_i64`, but `None` if it does not fit in
+    /// a `u16`.
+    fn as_u16(&self) -> Option<u16> {
+        self.as_i64().and_then(|value| u16::try_from(value).ok())
+    }
+    /// Gets the value of this node as a float, converting it if it is stored as another type.
+    /// Integers are converted to the nearest float, and strings are parsed after trimming
+    /// whitespace.
+    fn as_f64(&self) -> Option<f64> {
+        match self.dtype() {
+            Type::Integer => self.integer().map(|value| value as f64),
+            Type::Float => self.float(),
+            Type::String => self.string()?.trim().parse().ok(),
+            _ => None,
+        }
+    }
+    /// Gets the value of this node as a boolean. Numbers are `false` if they are zero and `true`
+    /// if they are one, and strings may be `true` or `false` in any case as well as those
+    /// numbers. This will be `None` for any other value.
+    fn as_bool(&self) -> Option<bool> {
+        match self.dtype() {
+            Type::String => {
+                let string = self.string()?.trim();
+                if string.eq_ignore_ascii_case("true") {
+                    Some(true)
+                } else if string.eq_ignore_ascii_case("false") {
+                    Some(false)
+                } else {
+                    self.as_f64().and_then(boolean)
+                }
+            },
+            _ => self.as_f64().and_then(boolean),
+        }
+    }
+    /// Gets the value of this node as a string. Strings are borrowed from the string table,
+    /// numbers are formatted, and vectors are formatted as `x,y`. This will be `None` for empty
+    /// nodes, bitmaps and audio.
+    fn to_string_lossy(&self) -> Option<Cow<'a, str>> {
+        match self.dtype() {
+            Type::String => self.string().map(Cow::Borrowed),
+            Type::Integer => self.integer().map(|value| Cow::Owned(value.to_string())),
+            Type::Float => self.float().map(|value| Cow::Owned(value.to_string())),
+            Type::Vector => self.vector().map(|(x, y)| Cow::Owned(format!("{},{}", x, y))),
+            _ => None,
+        }
+    }
+}
+
+/// Converts zero to `false` and one to `true`.
+fn boolean(value: f64) -> Option<bool> {
+    match value {
+        0. => Some(false),
+        1. => Some(true),
+        _ => None,
+    }
+}
+
+/// Truncates a float towards zero, if the result fits in an `i64`.
+fn truncate(value: f64) -> Option<i64> {
+    // Both bounds are exactly representable, unlike `i64::MAX`. NaN is never in range.
+    let min = i64::MIN as f64;
+    if (min..-min).contains(&value) {
+        Some(value as i64)
+    } else {
+        None
+    }
+}
+
+/// The value of a node, for handling every type of node in one `match`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value<'a> {
+    /// The node has no value.
+    Empty,
+    /// A signed integer.
+    Integer(i64),
+    /// A double precision float.
+    Float(f64),
+    /// A string from the string table.
+    String(&'a str),
+    /// A two dimensional vector, usually a position or an offset.
+    Vector(i32, i32),
+    /// A bitmap from the bitmap table.
+    Bitmap(Bitmap<'a>),
+    /// Some audio from the audio table.
+    Audio(Audio<'a>),
+    /// A node of a type this library does not know, with its raw type and data.
+    Unknown(u16, [u8; 8]),
+}
+
+impl<'a> Value<'a> {
+    /// Gets the type of node this value is from.
+    pub fn dtype(&self) -> Type {
+        match *self {
+            Value::Empty => Type::Empty,
+            Value::Integer(_) => Type::Integer,
+            Value::Float(_) => Type::Float,
+            Value::String(_) => Type::String,
+            Value::Vector(..) => Type::Vector,
+            Value::Bitmap(_) => Type::Bitmap,
+            Value::Audio(_) => Type::Audio,
+            Value::Unknown(dtype, _) => Type::Unknown(dtype),
+        }
+    }
+}
+
+/// Empty values are formatted as nothing, strings as they are, vectors as `(x, y)`, bitmaps and
+/// audio as a short description of their size, and unknown values as their type and data in hex.
+impl<'a> Display for Value<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match *self {
+            Value::Empty => Ok(()),
+            Value::Integer(value) => Display::fmt(&value, fmt),
+            Value::Float(value) => Display::fmt(&value, fmt),
+            Value::String(value) => Display::fmt(value, fmt),
+            Value::Vector(x, y) => write!(fmt, "({}, {})", x, y),
+            Value::Bitmap(bitmap) => write!(fmt, "{}x{} bitmap", bitmap.width(), bitmap.height()),
+            Value::Audio(audio) => write!(fmt, "{} bytes of audio", audio.data().len()),
+            Value::Unknown(dtype, data) => {
+                write!(fmt, "type {} ", dtype)?;
+                data.iter().try_for_each(|byte| write!(fmt, "{:02x}", byte))
+            },
+        }
+    }
+}
+
+/// The index of a node in the node table of an NX file.
+///
+/// Ids are stable for as long as the file is unchanged, so they can be stored in place of a
+/// `Node` and turned back into one with `File::node`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    /// The id of the root node.
+    pub const ROOT: NodeId = NodeId(0);
 }
 
 /// A node in an NX file.
@@ -41,9 +237,23 @@ pub struct Node<'a> {
 
 impl<'a> Node<'a> {
     /// Creates a Node from the data representing it and the file the data is from.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be a node from the node table of `file`.
+    #[inline]
+    pub(crate) unsafe fn construct(data: &'a repr::Node, file: &'a File) -> Node<'a> {
+        Node { data, file }
+    }
+    /// Gets the id of this node, which is its index in the node table.
     #[inline]
-    pub unsafe fn construct(data: &'a repr::Node, file: &'a File) -> Node<'a> {
-        Node { data: data, file: file }
+    pub fn id(&self) -> NodeId {
+        NodeId(self.file.node_index(self.data))
+    }
+    /// Gets the file this node is from.
+    #[inline]
+    pub fn file(&self) -> &'a File {
+        self.file
     }
     /// Gets whether or not the node is empty.
     #[inline]
@@ -55,12 +265,110 @@ impl<'a> Node<'a> {
     pub fn name(&self) -> &'a str {
         unsafe { self.file.get_str(self.data.name) }
     }
+    /// Gets the parent of this node, or `None` for the root node.
+    ///
+    /// This builds the parent index of the file the first time it is called.
+    #[inline]
+    pub fn parent(&self) -> Option<Node<'a>> {
+        self.file.parent_of(self.id().0).and_then(|index| self.file.node(NodeId(index)))
+    }
+    /// Gets an iterator over the ancestors of this node, starting with its parent and ending with
+    /// the root node.
+    #[inline]
+    pub fn ancestors(&self) -> Ancestors<'a> {
+        Ancestors { node: self.parent() }
+    }
+    /// Gets an iterator over the descendants of this node, depth first with each node before its
+    /// children. Use `Walker` to walk the subtree in other ways.
+    #[inline]
+    pub fn descendants(&self) -> Walk<'a> {
+        Walker::new().walk(*self)
+    }
+    /// Gets a parallel iterator over this node's children.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_iter(&self) -> ParNodes<'a> {
+        ParNodes::new(self.iter())
+    }
+    /// Gets a parallel iterator over the descendants of this node, in no particular order. Work
+    /// is split between threads by handing off subtrees which have not been visited yet.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_descendants(&self) -> ParDescendants<'a> {
+        ParDescendants::new(self.iter())
+    }
+    /// Gets the number of ancestors of this node. The root node has a depth of zero.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+    /// Gets the names of the ancestors of this node and of this node itself, separated by `/`.
+    /// The root node is not included, so its path is empty.
+    pub fn path(&self) -> String {
+        let mut names: Vec<_> = once(*self).chain(self.ancestors()).map(|node| node.name())
+            .collect();
+        names.pop();
+        names.reverse();
+        names.join("/")
+    }
+    /// Follows `path` from this node, returning the first segment which could not be found.
+    fn walk<'p>(&self, path: &'p str) -> Result<Node<'a>, &'p str> {
+        let mut node = *self;
+        for segment in path.split('/') {
+            node = match segment {
+                "" | "." => Some(node),
+                ".." => node.parent(),
+                _ => node.get(segment),
+            }.ok_or(segment)?;
+        }
+        Ok(node)
+    }
+    /// Gets the value of this node. This checks the type of the node once, unlike trying each
+    /// of the accessors of `GenericNode` in turn.
+    #[inline]
+    pub fn value(&self) -> Value<'a> {
+        let data = self.data.data;
+        unsafe {
+            match self.dtype() {
+                Type::Empty => Value::Empty,
+                Type::Integer => Value::Integer(transmute::<u64, repr::Integer>(data).value),
+                Type::Float => Value::Float(transmute::<u64, repr::Float>(data).value),
+                Type::String => {
+                    Value::String(self.file.get_str(transmute::<u64, repr::String>(data).index))
+                },
+                Type::Vector => {
+                    let vec = transmute::<u64, repr::Vector>(data);
+                    Value::Vector(vec.x, vec.y)
+                },
+                Type::Bitmap => {
+                    let bitmap = transmute::<u64, repr::Bitmap>(data);
+                    Value::Bitmap(Bitmap::construct(self.file.get_bitmap(bitmap.index),
+                        bitmap.width, bitmap.height))
+                },
+                Type::Audio => {
+                    let audio = transmute::<u64, repr::Audio>(data);
+                    Value::Audio(Audio::construct(self.file.get_audio(audio.index, audio.length)))
+                },
+                Type::Unknown(dtype) => Value::Unknown(dtype, data.to_le_bytes()),
+            }
+        }
+    }
+    /// Gets the type of this node exactly as it is stored in the file.
+    #[inline]
+    pub fn raw_type(&self) -> u16 {
+        self.data.dtype
+    }
+    /// Gets the 8 bytes of data of this node exactly as they are stored in the file. What they
+    /// mean depends on the type of the node.
+    #[inline]
+    pub fn raw_data(&self) -> [u8; 8] {
+        self.data.data.to_le_bytes()
+    }
     /// Gets an iterator over this node's children.
     #[inline]
     pub fn iter(&self) -> Nodes<'a> {
-        let data = unsafe { self.file.get_node(self.data.children) };
         Nodes {
-            data: data,
+            data: self.file.node_ptr(self.data.children),
             count: self.data.count,
             file: self.file,
         }
@@ -70,7 +378,7 @@ impl<'a> Node<'a> {
 impl<'a> GenericNode<'a> for Node<'a> {
     #[inline]
     fn get(&self, name: &str) -> Option<Node<'a>> {
-        let mut data = unsafe { self.file.get_node(self.data.children) as *const repr::Node };
+        let mut data = self.file.node_ptr(self.data.children);
         let mut count = self.data.count as isize;
         while count > 0 {
             let half = count / 2;
@@ -92,22 +400,13 @@ impl<'a> GenericNode<'a> for Node<'a> {
     }
     #[inline]
     fn dtype(&self) -> Type {
-        match self.data.dtype {
-            0 => Type::Empty,
-            1 => Type::Integer,
-            2 => Type::Float,
-            3 => Type::String,
-            4 => Type::Vector,
-            5 => Type::Bitmap,
-            6 => Type::Audio,
-            _ => Type::Empty
-        }
+        Type::from_raw(self.data.dtype)
     }
     #[inline]
     fn string(&self) -> Option<&'a str> {
         match self.dtype() {
             Type::String => Some(unsafe {
-                self.file.get_str(transmute::<_, repr::String>(self.data.data).index)
+                self.file.get_str(transmute::<u64, repr::String>(self.data.data).index)
             }),
             _ => None,
         }
@@ -115,14 +414,14 @@ impl<'a> GenericNode<'a> for Node<'a> {
     #[inline]
     fn integer(&self) -> Option<i64> {
         match self.dtype() {
-            Type::Integer => Some(unsafe { transmute::<_, repr::Integer>(self.data.data).value }),
+            Type::Integer => Some(unsafe { transmute::<u64, repr::Integer>(self.data.data).value }),
             _ => None,
         }
     }
     #[inline]
     fn float(&self) -> Option<f64> {
         match self.dtype() {
-            Type::Float => Some(unsafe { transmute::<_, repr::Float>(self.data.data).value }),
+            Type::Float => Some(unsafe { transmute::<u64, repr::Float>(self.data.data).value }),
             _ => None,
         }
     }
@@ -130,7 +429,7 @@ impl<'a> GenericNode<'a> for Node<'a> {
     fn vector(&self) -> Option<(i32, i32)> {
         match self.dtype() {
             Type::Vector => Some(unsafe {
-                let vec = transmute::<_, repr::Vector>(self.data.data);
+                let vec = transmute::<u64, repr::Vector>(self.data.data);
                 (vec.x, vec.y)
             }),
             _ => None,
@@ -140,7 +439,7 @@ impl<'a> GenericNode<'a> for Node<'a> {
     fn audio(&self) -> Option<Audio<'a>> {
         match self.dtype() {
             Type::Audio => Some(unsafe {
-                let audio = transmute::<_, repr::Audio>(self.data.data);
+                let audio = transmute::<u64, repr::Audio>(self.data.data);
                 Audio::construct(self.file.get_audio(audio.index, audio.length))
             }),
             _ => None,
@@ -150,68 +449,95 @@ impl<'a> GenericNode<'a> for Node<'a> {
     fn bitmap(&self) -> Option<Bitmap<'a>> {
         match self.dtype() {
             Type::Bitmap => Some(unsafe {
-                let bitmap = transmute::<_, repr::Bitmap>(self.data.data);
+                let bitmap = transmute::<u64, repr::Bitmap>(self.data.data);
                 Bitmap::construct(self.file.get_bitmap(bitmap.index), bitmap.width, bitmap.height)
             }),
             _ => None,
         }
     }
+    #[inline]
+    fn resolve(&self, path: &str) -> Option<Node<'a>> {
+        self.walk(path).ok()
+    }
+    fn try_resolve(&self, path: &str) -> Result<Node<'a>, Error> {
+        self.walk(path).map_err(|segment| Error::NotFound {
+            path: path.to_owned(),
+            segment: segment.to_owned(),
+        })
+    }
 }
 impl<'a> GenericNode<'a> for Option<Node<'a>> {
     #[inline]
     fn get(&self, name: &str) -> Option<Node<'a>> {
-        match self {
-            &Some(n) => n.get(name),
-            &None => None,
+        match *self {
+            Some(n) => n.get(name),
+            None => None,
         }
     }
     #[inline]
     fn dtype(&self) -> Type {
-        match self {
-            &Some(n) => n.dtype(),
-            &None => Type::Empty,
+        match *self {
+            Some(n) => n.dtype(),
+            None => Type::Empty,
         }
     }
     #[inline]
     fn string(&self) -> Option<&'a str> {
-        match self {
-            &Some(n) => n.string(),
-            &None => None,
+        match *self {
+            Some(n) => n.string(),
+            None => None,
         }
     }
     #[inline]
     fn integer(&self) -> Option<i64> {
-        match self {
-            &Some(n) => n.integer(),
-            &None => None,
+        match *self {
+            Some(n) => n.integer(),
+            None => None,
         }
     }
     #[inline]
     fn float(&self) -> Option<f64> {
-        match self {
-            &Some(n) => n.float(),
-            &None => None,
+        match *self {
+            Some(n) => n.float(),
+            None => None,
         }
     }
     #[inline]
     fn vector(&self) -> Option<(i32, i32)> {
-        match self {
-            &Some(n) => n.vector(),
-            &None => None,
+        match *self {
+            Some(n) => n.vector(),
+            None => None,
         }
     }
     #[inline]
     fn audio(&self) -> Option<Audio<'a>> {
-        match self {
-            &Some(n) => n.audio(),
-            &None => None,
+        match *self {
+            Some(n) => n.audio(),
+            None => None,
         }
     }
     #[inline]
     fn bitmap(&self) -> Option<Bitmap<'a>> {
-        match self {
-            &Some(n) => n.bitmap(),
-            &None => None,
+        match *self {
+            Some(n) => n.bitmap(),
+            None => None,
+        }
+    }
+    #[inline]
+    fn resolve(&self, path: &str) -> Option<Node<'a>> {
+        match *self {
+            Some(n) => n.resolve(path),
+            None => None,
+        }
+    }
+    /// If this is `None`, the first segment of the path is the one which could not be found.
+    fn try_resolve(&self, path: &str) -> Result<Node<'a>, Error> {
+        match *self {
+            Some(n) => n.try_resolve(path),
+            None => Err(Error::NotFound {
+                path: path.to_owned(),
+                segment: path.split('/').next().unwrap_or("").to_owned(),
+            }),
         }
     }
 }
@@ -219,7 +545,7 @@ impl<'a> GenericNode<'a> for Option<Node<'a>> {
 impl<'a> PartialEq for Node<'a> {
     #[inline]
     fn eq(&self, other: &Node) -> bool {
-        self.data as *const repr::Node == other.data as *const repr::Node
+        ptr::eq(self.data, other.data)
     }
 }
 
@@ -255,3 +581,53 @@ impl<'a> Iterator for Nodes<'a> {
     }
 }
 
+impl<'a> DoubleEndedIterator for Nodes<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Node<'a>> {
+        match self.count {
+            0 => None,
+            _ => {
+                self.count -= 1;
+                Some(Node {
+                    data: unsafe { &*self.data.offset(self.count as isize) },
+                    file: self.file
+                })
+            }
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for Nodes<'a> {}
+
+// `Nodes` only points into the node table of a file it borrows, like `&'a [repr::Node]`.
+unsafe impl<'a> Send for Nodes<'a> {}
+unsafe impl<'a> Sync for Nodes<'a> {}
+
+#[cfg(feature = "rayon")]
+impl<'a> Nodes<'a> {
+    /// Splits the remaining nodes into the first `index` nodes and the rest.
+    pub(crate) fn split_at(self, index: usize) -> (Nodes<'a>, Nodes<'a>) {
+        assert!(index <= self.count as usize);
+        let rest = Nodes {
+            data: unsafe { self.data.add(index) },
+            count: self.count - index as u16,
+            file: self.file,
+        };
+        (Nodes { count: index as u16, ..self }, rest)
+    }
+}
+
+/// An iterator over the ancestors of a node.
+pub struct Ancestors<'a> {
+    node: Option<Node<'a>>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = Node<'a>;
+    #[inline]
+    fn next(&mut self) -> Option<Node<'a>> {
+        let node = self.node?;
+        self.node = node.parent();
+        Some(node)
+    }
+}
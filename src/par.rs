@@ -0,0 +1,137 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Parallel iterators over nodes with rayon
+//!
+//! Nodes only borrow their file, which is `Sync`, so they can be handed to other threads freely.
+//! `Node::par_iter` splits the children of a node into ranges, and `Node::par_descendants` also
+//! hands off the subtrees which have not been visited yet, so work is spread across threads even
+//! when most of it is under a few children.
+use rayon::iter::plumbing::{bridge, bridge_unindexed, Consumer, Folder, Producer,
+    ProducerCallback, UnindexedConsumer, UnindexedProducer};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use node::{Node, Nodes};
+
+/// A parallel iterator over the children of a node, created by `Node::par_iter`.
+pub struct ParNodes<'a> {
+    nodes: Nodes<'a>,
+}
+
+impl<'a> ParNodes<'a> {
+    pub(crate) fn new(nodes: Nodes<'a>) -> ParNodes<'a> {
+        ParNodes { nodes }
+    }
+}
+
+impl<'a> ParallelIterator for ParNodes<'a> {
+    type Item = Node<'a>;
+    fn drive_unindexed<C: UnindexedConsumer<Node<'a>>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.nodes.len())
+    }
+}
+
+impl<'a> IndexedParallelIterator for ParNodes<'a> {
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    fn drive<C: Consumer<Node<'a>>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+    fn with_producer<CB: ProducerCallback<Node<'a>>>(self, callback: CB) -> CB::Output {
+        callback.callback(self.nodes)
+    }
+}
+
+impl<'a> Producer for Nodes<'a> {
+    type Item = Node<'a>;
+    type IntoIter = Nodes<'a>;
+    fn into_iter(self) -> Nodes<'a> {
+        self
+    }
+    fn split_at(self, index: usize) -> (Nodes<'a>, Nodes<'a>) {
+        Nodes::split_at(self, index)
+    }
+}
+
+/// A parallel iterator over the descendants of a node, created by `Node::par_descendants`.
+pub struct ParDescendants<'a> {
+    nodes: Nodes<'a>,
+}
+
+impl<'a> ParDescendants<'a> {
+    pub(crate) fn new(nodes: Nodes<'a>) -> ParDescendants<'a> {
+        ParDescendants { nodes }
+    }
+}
+
+impl<'a> ParallelIterator for ParDescendants<'a> {
+    type Item = Node<'a>;
+    fn drive_unindexed<C: UnindexedConsumer<Node<'a>>>(self, consumer: C) -> C::Result {
+        let producer = Subtrees { node: None, stack: vec![self.nodes] };
+        bridge_unindexed(producer, consumer)
+    }
+}
+
+/// Some of the descendants of a node, which are visited depth first by one thread at a time.
+struct Subtrees<'a> {
+    /// A node whose descendants are already on the stack.
+    node: Option<Node<'a>>,
+    /// Ranges of siblings which have not been visited yet, along with their descendants. The
+    /// ranges closer to the bottom are closer to the root, so they usually hold more work.
+    stack: Vec<Nodes<'a>>,
+}
+
+impl<'a> UnindexedProducer for Subtrees<'a> {
+    type Item = Node<'a>;
+    fn split(mut self) -> (Subtrees<'a>, Option<Subtrees<'a>>) {
+        loop {
+            if self.stack.len() > 1 {
+                let stack = vec![self.stack.remove(0)];
+                return (self, Some(Subtrees { node: None, stack }))
+            }
+            let mut nodes = match self.stack.pop() {
+                Some(nodes) => nodes,
+                None => return (self, None),
+            };
+            let len = nodes.len();
+            if len > 1 {
+                let (first, rest) = nodes.split_at(len / 2);
+                self.stack.push(first);
+                return (self, Some(Subtrees { node: None, stack: vec![rest] }))
+            }
+            if self.node.is_some() {
+                return (self, Some(Subtrees { node: None, stack: vec![nodes] }))
+            }
+            // A lone node is kept, and its children are split the next time around.
+            if let Some(node) = nodes.next() {
+                self.node = Some(node);
+                if !node.is_empty() {
+                    self.stack.push(node.iter());
+                }
+            }
+        }
+    }
+    fn fold_with<F: Folder<Node<'a>>>(mut self, mut folder: F) -> F {
+        if let Some(node) = self.node.take() {
+            folder = folder.consume(node);
+        }
+        while !folder.full() {
+            let next = match self.stack.last_mut() {
+                Some(nodes) => nodes.next(),
+                None => break,
+            };
+            match next {
+                Some(node) => {
+                    folder = folder.consume(node);
+                    if !node.is_empty() {
+                        self.stack.push(node.iter());
+                    }
+                },
+                None => drop(self.stack.pop()),
+            }
+        }
+        folder
+    }
+}
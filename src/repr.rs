@@ -1,8 +1,7 @@
 // Copyright © 2015-2018, Peter Atashian
 
 /// An NX file header.
-#[repr(packed)]
-#[allow(dead_code)]
+#[repr(C, packed)]
 pub struct Header {
     pub magic: u32,
     pub nodecount: u32,
@@ -16,7 +15,7 @@ pub struct Header {
 }
 
 /// The data contained by an NX node.
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Node {
     pub name: u32,
     pub children: u32,
@@ -25,7 +24,7 @@ pub struct Node {
     pub data: u64,
 }
 
-/// The types of NX nodes.
+/// The types of NX nodes. The discriminant of each known type is its type code in the file.
 #[repr(u16)]
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Type {
@@ -43,31 +42,67 @@ pub enum Type {
     Bitmap = 5,
     /// A node containing audio data.
     Audio = 6,
+    /// A node with a type this library does not know, holding the raw type. Files with such
+    /// nodes are only opened if `OpenOptions::allow_unknown_types` is set.
+    Unknown(u16),
+}
+
+impl Type {
+    /// Gets the type code of this type, as stored in a node.
+    #[inline]
+    pub const fn raw(self) -> u16 {
+        match self {
+            Type::Unknown(dtype) => dtype,
+            // `Type` is `repr(u16)`, so it starts with its discriminant.
+            known => unsafe { *(&known as *const Type as *const u16) },
+        }
+    }
+    /// Gets the type with the specified type code, as stored in a node.
+    #[inline]
+    pub const fn from_raw(dtype: u16) -> Type {
+        const EMPTY: u16 = Type::Empty.raw();
+        const INTEGER: u16 = Type::Integer.raw();
+        const FLOAT: u16 = Type::Float.raw();
+        const STRING: u16 = Type::String.raw();
+        const VECTOR: u16 = Type::Vector.raw();
+        const BITMAP: u16 = Type::Bitmap.raw();
+        const AUDIO: u16 = Type::Audio.raw();
+        match dtype {
+            EMPTY => Type::Empty,
+            INTEGER => Type::Integer,
+            FLOAT => Type::Float,
+            STRING => Type::String,
+            VECTOR => Type::Vector,
+            BITMAP => Type::Bitmap,
+            AUDIO => Type::Audio,
+            dtype => Type::Unknown(dtype),
+        }
+    }
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Integer {
     pub value: i64,
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Float {
     pub value: f64,
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct String {
     pub index: u32,
     pub _padding: u32,
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Vector {
     pub x: i32,
     pub y: i32,
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 #[allow(dead_code)]
 pub struct Bitmap {
     pub index: u32,
@@ -75,7 +110,7 @@ pub struct Bitmap {
     pub height: u16,
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 #[allow(dead_code)]
 pub struct Audio {
     pub index: u32,
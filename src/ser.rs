@@ -0,0 +1,489 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Serializing Rust types into NX files with serde
+//!
+//! Values map onto nodes as follows:
+//!
+//! * Structs and maps become nodes with a child for each field or entry. Map keys must be
+//!   strings, characters or integers. `None` fields do not create a child.
+//! * Sequences become nodes with children named `0`, `1` and so on. `None` elements do not
+//!   create a child but still take up a number, so only trailing `None` elements are lost.
+//! * Tuples and tuple structs of two integers which fit in `i32` become vectors. Other tuples
+//!   become nodes with numerically named children like sequences.
+//! * `bool`, `i8` to `i64` and `u8` to `u64` become integers, as do `i128` and `u128` if they fit
+//!   in `i64`. `f32` and `f64` become floats.
+//! * Strings and characters become strings.
+//! * Bytes become audio, and are expected to start with the wz audio header.
+//! * Units become empty nodes, unit variants become strings naming the variant, and other enum
+//!   variants become nodes with a single child named after the variant.
+//!
+//! The nodes are collected in a `Builder`, which sorts children by the bytes of their names when
+//! it writes the file, so `Node::get` can find them.
+use serde::ser::{self, Impossible, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant};
+use std::convert::TryFrom;
+use std::fmt::Display;
+use std::io::Write;
+
+use file::Error;
+use write::{Builder, Handle, NodeValue};
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::Serialize { path: None, reason: msg.to_string() }
+    }
+}
+
+/// Serializes `value` into a new builder, with `value` as the root node.
+pub fn to_builder<T: Serialize + ?Sized>(value: &T) -> Result<Builder, Error> {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    value.serialize(Serializer::new(&mut builder, root))?;
+    Ok(builder)
+}
+
+/// Serializes `value` as an NX file, with `value` as the root node.
+pub fn to_writer<T: Serialize + ?Sized, W: Write>(value: &T, out: W) -> Result<(), Error> {
+    to_builder(value)?.write(out)
+}
+
+/// Serializes `value` as an NX file in a new buffer, with `value` as the root node.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    to_builder(value)?.to_vec()
+}
+
+/// Where a value is serialized to.
+enum Slot {
+    /// An existing node.
+    Node(Handle),
+    /// A child which is only added if the value is not `None`.
+    Child(Handle, String),
+}
+
+/// A serializer writing a value into a node of a `Builder`.
+pub struct Serializer<'b> {
+    builder: &'b mut Builder,
+    slot: Slot,
+}
+
+impl<'b> Serializer<'b> {
+    /// Creates a serializer which sets the value and adds the children of `node`.
+    pub fn new(builder: &'b mut Builder, node: Handle) -> Serializer<'b> {
+        Serializer { builder, slot: Slot::Node(node) }
+    }
+    fn handle(&mut self) -> Handle {
+        match self.slot {
+            Slot::Node(node) => node,
+            Slot::Child(parent, ref name) => self.builder.add(parent, &**name, NodeValue::Empty),
+        }
+    }
+    fn set(mut self, value: NodeValue) -> Result<(), Error> {
+        let node = self.handle();
+        self.builder.set_value(node, value);
+        Ok(())
+    }
+    fn compound(mut self) -> Compound<'b> {
+        let node = self.handle();
+        Compound { builder: self.builder, node, index: 0, key: None, pair: Some(Vec::new()) }
+    }
+    /// Adds a child named after an enum variant and serializes the contents into it.
+    fn variant(mut self, variant: &str) -> Compound<'b> {
+        let node = self.handle();
+        let node = self.builder.add(node, variant, NodeValue::Empty);
+        Compound { builder: self.builder, node, index: 0, key: None, pair: Some(Vec::new()) }
+    }
+    fn integer<T>(self, value: T) -> Result<(), Error> where i64: TryFrom<T>, T: Display + Copy {
+        match i64::try_from(value) {
+            Ok(integer) => self.set(NodeValue::Integer(integer)),
+            Err(_) => Err(ser::Error::custom(format_args!("{} does not fit in an integer node",
+                value))),
+        }
+    }
+}
+
+impl<'b> ser::Serializer for Serializer<'b> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'b>;
+    type SerializeTuple = Compound<'b>;
+    type SerializeTupleStruct = Compound<'b>;
+    type SerializeTupleVariant = Compound<'b>;
+    type SerializeMap = Compound<'b>;
+    type SerializeStruct = Compound<'b>;
+    type SerializeStructVariant = Compound<'b>;
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v as i64))
+    }
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v.into()))
+    }
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v))
+    }
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.integer(v)
+    }
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.set(NodeValue::Integer(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.integer(v)
+    }
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.integer(v)
+    }
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.set(NodeValue::Float(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.set(NodeValue::Float(v))
+    }
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.set(NodeValue::String(v.to_string()))
+    }
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.set(NodeValue::String(v.to_owned()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.set(NodeValue::Audio(v.to_vec()))
+    }
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.set(NodeValue::Empty)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.set(NodeValue::Empty)
+    }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str)
+        -> Result<(), Error> {
+        self.set(NodeValue::String(variant.to_owned()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T)
+        -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32,
+        variant: &'static str, value: &T) -> Result<(), Error> {
+        let mut compound = self.variant(variant);
+        compound.pair = None;
+        value.serialize(Serializer::new(compound.builder, compound.node))
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'b>, Error> {
+        let mut compound = self.compound();
+        compound.pair = None;
+        Ok(compound)
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'b>, Error> {
+        let mut compound = self.compound();
+        if len != 2 {
+            compound.pair = None;
+        }
+        Ok(compound)
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize)
+        -> Result<Compound<'b>, Error> {
+        self.serialize_tuple(len)
+    }
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+        len: usize) -> Result<Compound<'b>, Error> {
+        let mut compound = self.variant(variant);
+        if len != 2 {
+            compound.pair = None;
+        }
+        Ok(compound)
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'b>, Error> {
+        Ok(self.compound())
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'b>, Error> {
+        Ok(self.compound())
+    }
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str,
+        _len: usize) -> Result<Compound<'b>, Error> {
+        Ok(self.variant(variant))
+    }
+}
+
+/// Serializes the children of a node.
+pub struct Compound<'b> {
+    builder: &'b mut Builder,
+    node: Handle,
+    /// The number of elements serialized so far, which names the next one.
+    index: usize,
+    /// The key of the map entry whose value is serialized next.
+    key: Option<String>,
+    /// The elements of a pair which might still become a vector. This is `None` once the pair
+    /// turned out not to be two integers, or if this is not a pair.
+    pair: Option<Vec<i32>>,
+}
+
+impl<'b> Compound<'b> {
+    fn child<T: Serialize + ?Sized>(&mut self, name: String, value: &T) -> Result<(), Error> {
+        let parent = self.node;
+        let result = value.serialize(Serializer {
+            builder: &mut *self.builder,
+            slot: Slot::Child(parent, name.clone()),
+        });
+        result.map_err(|err| match err {
+            Error::Serialize { path: None, reason } => {
+                let mut path = self.builder.path(parent);
+                if !path.is_empty() { path.push('/') }
+                path.push_str(&name);
+                Error::Serialize { path: Some(path), reason }
+            },
+            err => err,
+        })
+    }
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        if let Some(mut pair) = self.pair.take() {
+            if let Ok(coordinate) = value.serialize(Coordinate) {
+                pair.push(coordinate);
+                self.pair = Some(pair);
+                self.index += 1;
+                return Ok(())
+            }
+            for (index, coordinate) in pair.into_iter().enumerate() {
+                self.child(index.to_string(), &coordinate)?;
+            }
+        }
+        let name = self.index.to_string();
+        self.index += 1;
+        self.child(name, value)
+    }
+    fn finish(self) -> Result<(), Error> {
+        if let Some(pair) = self.pair {
+            if let [x, y] = *pair {
+                self.builder.set_value(self.node, NodeValue::Vector(x, y));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'b> SerializeSeq for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'b> SerializeTuple for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'b> SerializeTupleStruct for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'b> SerializeTupleVariant for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'b> SerializeMap for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(Key).map_err(|err| match err {
+            Error::Serialize { path: None, reason } => Error::Serialize {
+                path: Some(self.builder.path(self.node)),
+                reason,
+            },
+            err => err,
+        })?;
+        self.key = Some(key);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        self.child(key, value)
+    }
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'b> SerializeStruct for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T)
+        -> Result<(), Error> {
+        self.child(key.to_owned(), value)
+    }
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'b> SerializeStructVariant for Compound<'b> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T)
+        -> Result<(), Error> {
+        self.child(key.to_owned(), value)
+    }
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Turns map keys into node names.
+struct Key;
+
+const KEY: &str = "map keys must be strings, characters or integers";
+
+/// Accepts an integer which fits in `i32`, to find out whether a pair is a vector. Everything
+/// else is rejected without side effects, so the value can be serialized again as a node.
+struct Coordinate;
+
+const COORDINATE: &str = "not an integer which fits in `i32`";
+
+macro_rules! reject {
+    ($reason:expr; $($method:ident($($arg:ty),*);)*) => {
+        $(fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Error> {
+            Err(ser::Error::custom($reason))
+        })*
+    };
+}
+
+macro_rules! reject_compound {
+    ($reason:expr) => {
+        type SerializeSeq = Impossible<Self::Ok, Error>;
+        type SerializeTuple = Impossible<Self::Ok, Error>;
+        type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+        type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+        type SerializeMap = Impossible<Self::Ok, Error>;
+        type SerializeStruct = Impossible<Self::Ok, Error>;
+        type SerializeStructVariant = Impossible<Self::Ok, Error>;
+        fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<Self::Ok, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, _: &T)
+            -> Result<Self::Ok, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _: &'static str, _: u32,
+            _: &'static str, _: &T) -> Result<Self::Ok, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_tuple_struct(self, _: &'static str, _: usize)
+            -> Result<Self::SerializeTupleStruct, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize)
+            -> Result<Self::SerializeTupleVariant, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_struct(self, _: &'static str, _: usize)
+            -> Result<Self::SerializeStruct, Error> {
+            Err(ser::Error::custom($reason))
+        }
+        fn serialize_struct_variant(self, _: &'static str, _: u32, _: &'static str, _: usize)
+            -> Result<Self::SerializeStructVariant, Error> {
+            Err(ser::Error::custom($reason))
+        }
+    };
+}
+
+impl ser::Serializer for Key {
+    type Ok = String;
+    type Error = Error;
+    reject_compound!(KEY);
+    reject! {
+        KEY;
+        serialize_bool(bool); serialize_f32(f32); serialize_f64(f64); serialize_bytes(&[u8]);
+        serialize_none(); serialize_unit(); serialize_unit_struct(&'static str);
+    }
+    fn serialize_i8(self, v: i8) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_i16(self, v: i16) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_i32(self, v: i32) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_i64(self, v: i64) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_i128(self, v: i128) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_u8(self, v: u8) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_u16(self, v: u16) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_u32(self, v: u32) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_u64(self, v: u64) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_u128(self, v: u128) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_char(self, v: char) -> Result<String, Error> { Ok(v.to_string()) }
+    fn serialize_str(self, v: &str) -> Result<String, Error> { Ok(v.to_owned()) }
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str)
+        -> Result<String, Error> {
+        Ok(variant.to_owned())
+    }
+}
+
+impl ser::Serializer for Coordinate {
+    type Ok = i32;
+    type Error = Error;
+    reject_compound!(COORDINATE);
+    reject! {
+        COORDINATE;
+        serialize_bool(bool); serialize_f32(f32); serialize_f64(f64); serialize_bytes(&[u8]);
+        serialize_none(); serialize_unit(); serialize_unit_struct(&'static str);
+        serialize_char(char); serialize_str(&str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+    fn serialize_i8(self, v: i8) -> Result<i32, Error> { Ok(v.into()) }
+    fn serialize_i16(self, v: i16) -> Result<i32, Error> { Ok(v.into()) }
+    fn serialize_i32(self, v: i32) -> Result<i32, Error> { Ok(v) }
+    fn serialize_i64(self, v: i64) -> Result<i32, Error> { coordinate(v) }
+    fn serialize_i128(self, v: i128) -> Result<i32, Error> { coordinate(v) }
+    fn serialize_u8(self, v: u8) -> Result<i32, Error> { Ok(v.into()) }
+    fn serialize_u16(self, v: u16) -> Result<i32, Error> { Ok(v.into()) }
+    fn serialize_u32(self, v: u32) -> Result<i32, Error> { coordinate(v) }
+    fn serialize_u64(self, v: u64) -> Result<i32, Error> { coordinate(v) }
+    fn serialize_u128(self, v: u128) -> Result<i32, Error> { coordinate(v) }
+}
+
+fn coordinate<T>(value: T) -> Result<i32, Error> where i32: TryFrom<T> {
+    i32::try_from(value).map_err(|_| ser::Error::custom("out of range"))
+}
@@ -0,0 +1,247 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Walking every node in a subtree
+//!
+//! A `Walker` is configured once and then walks any number of subtrees. Each walk is an iterator
+//! which keeps its own stack or queue, so deep trees cannot overflow the call stack. Looping over
+//! a walk with `while let Some(node) = walk.next()` rather than `for` allows calling
+//! `Walk::prune` to skip the descendants of a node, and `Walk::path` and `Walk::depth` to find
+//! out where it is.
+use std::collections::VecDeque;
+
+use node::{GenericNode, Node, Nodes, Type};
+
+/// The order in which a walk visits nodes.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Order {
+    /// Depth first, visiting each node before its descendants.
+    #[default]
+    PreOrder,
+    /// Depth first, visiting each node after its descendants.
+    PostOrder,
+    /// Breadth first, visiting every node at one depth before any node at the next.
+    BreadthFirst,
+}
+
+/// Options for walking a subtree.
+#[derive(Clone, Debug, Default)]
+pub struct Walker {
+    order: Order,
+    max_depth: Option<usize>,
+    dtypes: Option<Vec<Type>>,
+    paths: bool,
+    root: bool,
+}
+
+impl Walker {
+    /// Creates options for a pre-order walk of every descendant, without the node the walk
+    /// starts from.
+    pub fn new() -> Walker {
+        Walker::default()
+    }
+    /// Sets the order in which nodes are visited.
+    pub fn order(&mut self, order: Order) -> &mut Walker {
+        self.order = order;
+        self
+    }
+    /// Sets how deep the walk goes. The node the walk starts from has a depth of zero and its
+    /// children a depth of one, so a maximum depth of one only visits the children.
+    pub fn max_depth(&mut self, max_depth: usize) -> &mut Walker {
+        self.max_depth = Some(max_depth);
+        self
+    }
+    /// Only yields nodes of one of the given types. The descendants of other nodes are still
+    /// visited.
+    pub fn dtypes(&mut self, dtypes: &[Type]) -> &mut Walker {
+        self.dtypes = Some(dtypes.to_vec());
+        self
+    }
+    /// Sets whether the walk keeps track of the path of each node, for `Walk::path`.
+    pub fn paths(&mut self, paths: bool) -> &mut Walker {
+        self.paths = paths;
+        self
+    }
+    /// Sets whether the node the walk starts from is yielded as well as its descendants.
+    pub fn include_root(&mut self, root: bool) -> &mut Walker {
+        self.root = root;
+        self
+    }
+    /// Starts walking the subtree of `node`.
+    pub fn walk<'a>(&self, node: Node<'a>) -> Walk<'a> {
+        Walk {
+            options: self.clone(),
+            start: Some(node),
+            stack: Vec::new(),
+            queue: VecDeque::new(),
+            pending: None,
+            depth: 0,
+            path: String::new(),
+            names: Vec::new(),
+        }
+    }
+}
+
+/// A walk over a subtree, created by `Walker::walk` or `Node::descendants`.
+///
+/// `depth`, `path` and `prune` refer to the node most recently returned by `next`, so they can
+/// be used from a `while let` loop.
+pub struct Walk<'a> {
+    options: Walker,
+    /// The node the walk starts from, until it has been visited.
+    start: Option<Node<'a>>,
+    /// The children still to be visited of each node on the way down, for depth first walks.
+    stack: Vec<Frame<'a>>,
+    /// The nodes still to be visited and their depths, for breadth first walks.
+    queue: VecDeque<(Node<'a>, usize)>,
+    /// The node most recently visited, if its children have not been visited yet and it has
+    /// not been pruned.
+    pending: Option<Node<'a>>,
+    depth: usize,
+    path: String,
+    /// The names of the ancestors of a node, reused to build paths in breadth first walks.
+    names: Vec<&'a str>,
+}
+
+/// A node being walked depth first.
+struct Frame<'a> {
+    node: Node<'a>,
+    children: Nodes<'a>,
+    /// The length of the path of `node`.
+    len: usize,
+}
+
+impl<'a> Walk<'a> {
+    /// The depth of the current node, relative to the node the walk started from.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    /// The path of the current node relative to the node the walk started from, which has an
+    /// empty path. This is always empty unless paths were enabled with `Walker::paths`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Skips the descendants of the current node. This has no effect on post-order walks, which
+    /// have already visited them.
+    pub fn prune(&mut self) {
+        self.pending = None;
+    }
+    fn expands(&self, node: Node, depth: usize) -> bool {
+        !node.is_empty() && self.options.max_depth.is_none_or(|max| depth < max)
+    }
+    /// Appends the name of a child to the path of its parent, which has length `len`.
+    fn enter(&mut self, len: usize, name: &str) {
+        if self.options.paths {
+            self.path.truncate(len);
+            if !self.path.is_empty() { self.path.push('/') }
+            self.path.push_str(name);
+        }
+    }
+    fn pre_order(&mut self) -> Option<Node<'a>> {
+        if let Some(node) = self.start.take() {
+            self.pending = Some(node);
+            return Some(node)
+        }
+        if let Some(node) = self.pending.take() {
+            if self.expands(node, self.depth) {
+                self.stack.push(Frame { node, children: node.iter(), len: self.path.len() });
+            }
+        }
+        loop {
+            let (len, child) = {
+                let frame = self.stack.last_mut()?;
+                (frame.len, frame.children.next())
+            };
+            match child {
+                Some(child) => {
+                    self.enter(len, child.name());
+                    self.depth = self.stack.len();
+                    self.pending = Some(child);
+                    return Some(child)
+                },
+                None => drop(self.stack.pop()),
+            }
+        }
+    }
+    fn post_order(&mut self) -> Option<Node<'a>> {
+        if let Some(node) = self.start.take() {
+            if !self.expands(node, 0) {
+                return Some(node)
+            }
+            self.stack.push(Frame { node, children: node.iter(), len: 0 });
+        }
+        loop {
+            let (len, child) = {
+                let frame = self.stack.last_mut()?;
+                (frame.len, frame.children.next())
+            };
+            match child {
+                Some(child) => {
+                    self.enter(len, child.name());
+                    let depth = self.stack.len();
+                    if self.expands(child, depth) {
+                        self.stack.push(Frame { node: child, children: child.iter(),
+                            len: self.path.len() });
+                        continue
+                    }
+                    self.depth = depth;
+                    return Some(child)
+                },
+                None => {
+                    let frame = self.stack.pop()?;
+                    self.path.truncate(frame.len);
+                    self.depth = self.stack.len();
+                    return Some(frame.node)
+                },
+            }
+        }
+    }
+    fn breadth_first(&mut self) -> Option<Node<'a>> {
+        if let Some(node) = self.start.take() {
+            self.queue.push_back((node, 0));
+        }
+        if let Some(node) = self.pending.take() {
+            if self.expands(node, self.depth) {
+                let depth = self.depth + 1;
+                self.queue.extend(node.iter().map(|child| (child, depth)));
+            }
+        }
+        let (node, depth) = self.queue.pop_front()?;
+        self.depth = depth;
+        self.pending = Some(node);
+        if self.options.paths {
+            // Nodes are not visited next to their parents, so the path is rebuilt from the
+            // ancestors of the node, which needs the parent index of the file.
+            self.names.clear();
+            self.names.push(node.name());
+            self.names.extend(node.ancestors().take(depth.saturating_sub(1)).map(|n| n.name()));
+            self.path.clear();
+            if depth > 0 {
+                for (index, name) in self.names.iter().rev().enumerate() {
+                    if index > 0 { self.path.push('/') }
+                    self.path.push_str(name);
+                }
+            }
+        }
+        Some(node)
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Node<'a>;
+    fn next(&mut self) -> Option<Node<'a>> {
+        loop {
+            let node = match self.options.order {
+                Order::PreOrder => self.pre_order(),
+                Order::PostOrder => self.post_order(),
+                Order::BreadthFirst => self.breadth_first(),
+            }?;
+            // Only the node the walk starts from has a depth of zero.
+            if self.depth == 0 && !self.options.root {
+                continue
+            }
+            match self.options.dtypes {
+                Some(ref dtypes) if !dtypes.contains(&node.dtype()) => continue,
+                _ => return Some(node),
+            }
+        }
+    }
+}
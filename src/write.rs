@@ -0,0 +1,352 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Writing NX files
+//!
+//! A `Builder` holds a tree of nodes in memory which is then written out as a PKG4 file. Nodes
+//! are laid out breadth-first so the children of every node are contiguous, and children are
+//! sorted by the bytes of their names so that `Node::get` can find them.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::io::{BufWriter, Error as IoError, Write};
+use std::mem::size_of;
+
+use bitmap::{Bitmap, Compression};
+use file::Error;
+use repr::{self, Type};
+
+/// The value of a node being written. Values read back from a file are `nx::Value`, which
+/// borrows from the file instead of owning its data.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NodeValue {
+    /// A node containing no data.
+    Empty,
+    /// A node containing integer data.
+    Integer(i64),
+    /// A node containing floating-point data.
+    Float(f64),
+    /// A node containing string data.
+    String(String),
+    /// A node containing vector (or point) data.
+    Vector(i32, i32),
+    /// A node containing bitmap data.
+    Bitmap {
+        /// The width in pixels.
+        width: u16,
+        /// The height in pixels.
+        height: u16,
+        /// The LZ4 compressed pixel data, exactly as it is stored in the file.
+        data: Vec<u8>,
+    },
+    /// A node containing audio data, including the wz audio header.
+    Audio(Vec<u8>),
+    /// A node of a type this library does not know, with its raw type and data. The type must
+    /// not be one of the known types.
+    Unknown(u16, [u8; 8]),
+}
+
+impl NodeValue {
+    /// Creates a bitmap value by compressing BGRA8 pixel data.
+    pub fn bitmap(pixels: &[u8], width: u16, height: u16, compression: Compression) -> NodeValue {
+        NodeValue::Bitmap {
+            width,
+            height,
+            data: Bitmap::compress_with(pixels, width, height, compression),
+        }
+    }
+}
+
+/// A handle to a node in a `Builder`.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct Handle(usize);
+
+/// A node in a `Builder`.
+struct Entry {
+    name: String,
+    value: NodeValue,
+    parent: Option<usize>,
+    children: BTreeMap<String, usize>,
+}
+
+/// Builds an NX file from a tree of nodes.
+pub struct Builder {
+    entries: Vec<Entry>,
+}
+
+impl Builder {
+    /// Creates a builder containing only an empty root node.
+    pub fn new() -> Builder {
+        Builder {
+            entries: vec![Entry {
+                name: String::new(),
+                value: NodeValue::Empty,
+                parent: None,
+                children: BTreeMap::new(),
+            }],
+        }
+    }
+    /// Gets the root node.
+    #[inline]
+    pub fn root(&self) -> Handle {
+        Handle(0)
+    }
+    /// Adds a child with the specified name and value to `parent`. If `parent` already has a
+    /// child with that name, its value is replaced instead.
+    pub fn add<S>(&mut self, parent: Handle, name: S, value: NodeValue) -> Handle
+        where S: Into<String> {
+        let name = name.into();
+        if let Some(&index) = self.entries[parent.0].children.get(&name) {
+            self.entries[index].value = value;
+            return Handle(index)
+        }
+        let index = self.entries.len();
+        self.entries[parent.0].children.insert(name.clone(), index);
+        self.entries.push(Entry {
+            name,
+            value,
+            parent: Some(parent.0),
+            children: BTreeMap::new(),
+        });
+        Handle(index)
+    }
+    /// Gets the child of `parent` with the specified name.
+    #[inline]
+    pub fn get(&self, parent: Handle, name: &str) -> Option<Handle> {
+        self.entries[parent.0].children.get(name).map(|&index| Handle(index))
+    }
+    /// Gets the value of a node.
+    #[inline]
+    pub fn value(&self, node: Handle) -> &NodeValue {
+        &self.entries[node.0].value
+    }
+    /// Replaces the value of a node.
+    #[inline]
+    pub fn set_value(&mut self, node: Handle, value: NodeValue) {
+        self.entries[node.0].value = value;
+    }
+    /// Number of nodes in the builder, including the root node.
+    #[inline]
+    pub fn node_count(&self) -> usize {
+        self.entries.len()
+    }
+    /// Gets the names of the ancestors of a node and of the node itself, separated by `/`.
+    /// The root node is not included, so its path is empty.
+    pub fn path(&self, node: Handle) -> String {
+        let mut index = node.0;
+        let mut names = Vec::new();
+        while let Some(parent) = self.entries[index].parent {
+            names.push(&*self.entries[index].name);
+            index = parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+    /// Writes the NX file to a new buffer.
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        self.write(&mut out)?;
+        Ok(out)
+    }
+    /// Writes the NX file.
+    pub fn write<W>(&self, out: W) -> Result<(), Error> where W: Write {
+        // Lay the nodes out breadth-first so the children of each node are contiguous.
+        let mut order = Vec::with_capacity(self.entries.len());
+        let mut first = vec![0u32; self.entries.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+        order.push(0);
+        while let Some(index) = queue.pop_front() {
+            let entry = &self.entries[index];
+            if entry.children.len() > u16::MAX as usize {
+                return Err(Error::TooManyChildren {
+                    path: self.path(Handle(index)),
+                    count: entry.children.len(),
+                })
+            }
+            first[index] = order.len() as u32;
+            for &child in entry.children.values() {
+                order.push(child);
+                queue.push_back(child);
+            }
+        }
+        let mut strings = Strings::default();
+        let mut bitmaps = Vec::new();
+        let mut audio = Vec::new();
+        let mut nodes = Vec::with_capacity(order.len());
+        for &index in &order {
+            let entry = &self.entries[index];
+            let (dtype, data) = match entry.value {
+                NodeValue::Empty => (Type::Empty, [0; 8]),
+                NodeValue::Integer(value) => (Type::Integer, value.to_le_bytes()),
+                NodeValue::Float(value) => (Type::Float, value.to_le_bytes()),
+                NodeValue::String(ref value) => (Type::String, pair(strings.add(value)?, 0)),
+                NodeValue::Vector(x, y) => (Type::Vector, pair(x as u32, y as u32)),
+                NodeValue::Bitmap { width, height, ref data } => {
+                    let index = bitmaps.len() as u32;
+                    bitmaps.push(&data[..]);
+                    (Type::Bitmap, pair(index, width as u32 | (height as u32) << 16))
+                },
+                NodeValue::Audio(ref data) => {
+                    let length = u32::try_from(data.len()).map_err(|_| Error::AudioTooLong {
+                        path: self.path(Handle(index)),
+                        length: data.len(),
+                    })?;
+                    let index = audio.len() as u32;
+                    audio.push(&data[..]);
+                    (Type::Audio, pair(index, length))
+                },
+                NodeValue::Unknown(dtype, data) => match Type::from_raw(dtype) {
+                    Type::Unknown(_) => (Type::Unknown(dtype), data),
+                    _ => return Err(Error::KnownType { path: self.path(Handle(index)), dtype }),
+                },
+            };
+            nodes.push(NodeRecord {
+                name: strings.add(&entry.name)?,
+                children: if entry.children.is_empty() { 0 } else { first[index] },
+                count: entry.children.len() as u16,
+                dtype: dtype.raw(),
+                data,
+            });
+        }
+        // Work out where every table and blob goes before writing anything.
+        let mut offset = size_of::<repr::Header>() as u64;
+        let nodeoffset = align(offset, 4);
+        offset = nodeoffset + (nodes.len() * size_of::<repr::Node>()) as u64;
+        let stringoffset = align(offset, 8);
+        offset = stringoffset + (strings.list.len() * size_of::<u64>()) as u64;
+        let stringdata = strings.list.iter().map(|string| {
+            let at = align(offset, 2);
+            offset = at + 2 + string.len() as u64;
+            at
+        }).collect::<Vec<_>>();
+        let bitmapoffset = align(offset, 8);
+        offset = bitmapoffset + (bitmaps.len() * size_of::<u64>()) as u64;
+        let bitmapdata = bitmaps.iter().map(|bitmap| {
+            let at = align(offset, 8);
+            offset = at + 4 + bitmap.len() as u64;
+            at
+        }).collect::<Vec<_>>();
+        let audiooffset = align(offset, 8);
+        offset = audiooffset + (audio.len() * size_of::<u64>()) as u64;
+        let audiodata = audio.iter().map(|audio| {
+            let at = align(offset, 8);
+            offset = at + audio.len() as u64;
+            at
+        }).collect::<Vec<_>>();
+        let mut out = Output { out: BufWriter::new(out), offset: 0 };
+        out.bytes(&0x34474B50u32.to_le_bytes())?;
+        out.bytes(&(nodes.len() as u32).to_le_bytes())?;
+        out.bytes(&nodeoffset.to_le_bytes())?;
+        out.bytes(&(strings.list.len() as u32).to_le_bytes())?;
+        out.bytes(&stringoffset.to_le_bytes())?;
+        out.bytes(&(bitmaps.len() as u32).to_le_bytes())?;
+        out.bytes(&bitmapoffset.to_le_bytes())?;
+        out.bytes(&(audio.len() as u32).to_le_bytes())?;
+        out.bytes(&audiooffset.to_le_bytes())?;
+        out.pad(nodeoffset)?;
+        for node in &nodes {
+            out.bytes(&node.name.to_le_bytes())?;
+            out.bytes(&node.children.to_le_bytes())?;
+            out.bytes(&node.count.to_le_bytes())?;
+            out.bytes(&node.dtype.to_le_bytes())?;
+            out.bytes(&node.data)?;
+        }
+        out.pad(stringoffset)?;
+        for &at in &stringdata {
+            out.bytes(&at.to_le_bytes())?;
+        }
+        for (string, &at) in strings.list.iter().zip(&stringdata) {
+            out.pad(at)?;
+            out.bytes(&(string.len() as u16).to_le_bytes())?;
+            out.bytes(string.as_bytes())?;
+        }
+        out.pad(bitmapoffset)?;
+        for &at in &bitmapdata {
+            out.bytes(&at.to_le_bytes())?;
+        }
+        for (bitmap, &at) in bitmaps.iter().zip(&bitmapdata) {
+            out.pad(at)?;
+            out.bytes(&(bitmap.len() as u32).to_le_bytes())?;
+            out.bytes(bitmap)?;
+        }
+        out.pad(audiooffset)?;
+        for &at in &audiodata {
+            out.bytes(&at.to_le_bytes())?;
+        }
+        for (audio, &at) in audio.iter().zip(&audiodata) {
+            out.pad(at)?;
+            out.bytes(audio)?;
+        }
+        out.out.flush()?;
+        Ok(())
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+/// A node as it will be written to the node table.
+struct NodeRecord {
+    name: u32,
+    children: u32,
+    count: u16,
+    dtype: u16,
+    data: [u8; 8],
+}
+
+/// The string table being built, with each distinct string stored once.
+#[derive(Default)]
+struct Strings<'a> {
+    list: Vec<&'a str>,
+    indices: HashMap<&'a str, u32>,
+}
+
+impl<'a> Strings<'a> {
+    /// Gets the index of a string, adding it to the table if it is not there yet.
+    fn add(&mut self, string: &'a str) -> Result<u32, Error> {
+        if let Some(&index) = self.indices.get(string) {
+            return Ok(index)
+        }
+        if string.len() > u16::MAX as usize {
+            return Err(Error::StringTooLong { length: string.len() })
+        }
+        let index = self.list.len() as u32;
+        self.list.push(string);
+        self.indices.insert(string, index);
+        Ok(index)
+    }
+}
+
+/// A writer which keeps track of how many bytes have been written.
+struct Output<W> {
+    out: W,
+    offset: u64,
+}
+
+impl<W> Output<W> where W: Write {
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), IoError> {
+        self.out.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+    /// Writes zeroes up to the specified offset, which is never more than 8 bytes away.
+    fn pad(&mut self, offset: u64) -> Result<(), IoError> {
+        let len = offset.saturating_sub(self.offset) as usize;
+        self.bytes(&[0; 8][..len])
+    }
+}
+
+/// Rounds `offset` up to a multiple of `to`.
+fn align(offset: u64, to: u64) -> u64 {
+    offset.div_ceil(to) * to
+}
+
+/// Packs two `u32` into the eight bytes of node data.
+fn pair(a: u32, b: u32) -> [u8; 8] {
+    let mut data = [0; 8];
+    data[..4].copy_from_slice(&a.to_le_bytes());
+    data[4..].copy_from_slice(&b.to_le_bytes());
+    data
+}
@@ -0,0 +1,749 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Reading WZ archives
+//!
+//! WZ is the archive format NX files are usually converted from. An archive is read into a tree
+//! of `Node`s, with directories and `.img` files as nodes containing other nodes, which can be
+//! browsed directly or written out as an NX file with `Node::to_builder`.
+
+use aes::Aes256;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::cipher::generic_array::GenericArray;
+use flate2::read::ZlibDecoder;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use bitmap::Compression;
+use file::Error;
+use write::{Builder, Handle, NodeValue};
+
+/// The key strings in an archive are encrypted with.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Encryption {
+    /// The key used by Global MapleStory.
+    Gms,
+    /// The key used by Korean MapleStory and MapleSEA.
+    Kms,
+    /// No key, used by archives which are not encrypted.
+    None,
+}
+
+impl Encryption {
+    /// Gets the initialization vector the key is generated from.
+    fn iv(self) -> [u8; 4] {
+        match self {
+            Encryption::Gms => [0x4D, 0x23, 0xC7, 0x2B],
+            Encryption::Kms => [0xB9, 0x7D, 0x63, 0xE9],
+            Encryption::None => [0; 4],
+        }
+    }
+}
+
+/// The AES key the WZ key is generated with.
+const AES_KEY: [u8; 32] = [
+    0x13, 0, 0, 0, 0x08, 0, 0, 0, 0x06, 0, 0, 0, 0xB4, 0, 0, 0,
+    0x1B, 0, 0, 0, 0x0F, 0, 0, 0, 0x33, 0, 0, 0, 0x52, 0, 0, 0,
+];
+
+/// The constant mixed into every encrypted offset.
+const OFFSET_KEY: u32 = 0x581C3F6D;
+
+/// How deeply properties may be nested inside each other before an image is rejected, so that
+/// reading them cannot overflow the stack.
+const MAX_DEPTH: usize = 256;
+
+/// The WZ key, which is generated as it is needed by repeatedly encrypting the initialization
+/// vector with AES.
+struct Key {
+    cipher: Option<Aes256>,
+    iv: [u8; 4],
+    bytes: Vec<u8>,
+}
+
+impl Key {
+    fn new(encryption: Encryption) -> Key {
+        let iv = encryption.iv();
+        Key {
+            cipher: match iv {
+                [0, 0, 0, 0] => None,
+                _ => Some(Aes256::new(GenericArray::from_slice(&AES_KEY))),
+            },
+            iv,
+            bytes: Vec::new(),
+        }
+    }
+    /// Gets at least the first `len` bytes of the key.
+    fn get(&mut self, len: usize) -> &[u8] {
+        while self.bytes.len() < len {
+            let at = self.bytes.len();
+            let mut block = GenericArray::from([0u8; 16]);
+            for (i, byte) in block.iter_mut().enumerate() {
+                *byte = if at == 0 { self.iv[i % 4] } else { self.bytes[at - 16 + i] };
+            }
+            match self.cipher {
+                Some(ref cipher) => cipher.encrypt_block(&mut block),
+                None => block = GenericArray::from([0u8; 16]),
+            }
+            self.bytes.extend_from_slice(&block);
+        }
+        &self.bytes[..len]
+    }
+}
+
+/// The value of a node in a WZ archive.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    /// A node containing no data, such as a directory, an image or a property list.
+    Empty,
+    /// A short, int or long property.
+    Integer(i64),
+    /// A float or double property.
+    Float(f64),
+    /// A string property.
+    String(String),
+    /// A vector property.
+    Vector(i32, i32),
+    /// A canvas property.
+    Canvas(Canvas),
+    /// A sound property.
+    Sound(Sound),
+    /// A UOL property, which is a path relative to the parent of the node. It is written to NX
+    /// files as a string, which `Node::follow` only follows if it starts with `../`.
+    Uol(String),
+}
+
+/// A node in a WZ archive.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Node {
+    /// The name of the node.
+    pub name: String,
+    /// The value of the node.
+    pub value: Value,
+    /// The children of the node, in the order they are stored in the archive.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(name: String, value: Value) -> Node {
+        Node { name, value, children: Vec::new() }
+    }
+    /// Gets the child node of the specified name.
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+    /// Gets the node at the specified path relative to this node, split on `/`.
+    pub fn resolve(&self, path: &str) -> Option<&Node> {
+        path.split('/').filter(|segment| !segment.is_empty())
+            .try_fold(self, |node, segment| node.get(segment))
+    }
+    /// Writes the children of this node into the root of a new NX builder. Canvases are
+    /// decoded and compressed with `compression`.
+    pub fn to_builder(&self, compression: Compression) -> Result<Builder, Error> {
+        let mut builder = Builder::new();
+        let root = builder.root();
+        for child in &self.children {
+            child.write(&mut builder, root, compression)?;
+        }
+        Ok(builder)
+    }
+    /// Adds this node and all of its children to an NX builder as a child of `parent`.
+    pub fn write(&self, builder: &mut Builder, parent: Handle, compression: Compression)
+        -> Result<Handle, Error> {
+        let value = match self.value {
+            Value::Empty => NodeValue::Empty,
+            Value::Integer(value) => NodeValue::Integer(value),
+            Value::Float(value) => NodeValue::Float(value),
+            Value::String(ref value) | Value::Uol(ref value) => NodeValue::String(value.clone()),
+            Value::Vector(x, y) => NodeValue::Vector(x, y),
+            Value::Canvas(ref canvas) => {
+                NodeValue::bitmap(&canvas.pixels()?, canvas.width, canvas.height, compression)
+            },
+            Value::Sound(ref sound) => NodeValue::Audio(sound.to_audio()),
+        };
+        let handle = builder.add(parent, &*self.name, value);
+        for child in &self.children {
+            child.write(builder, handle, compression)?;
+        }
+        Ok(handle)
+    }
+}
+
+/// An image stored in a canvas property.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Canvas {
+    /// The width in pixels.
+    pub width: u16,
+    /// The height in pixels.
+    pub height: u16,
+    /// The pixel format.
+    pub format: u32,
+    /// The scale the image is stored at, as a power of two.
+    pub scale: u8,
+    /// The zlib compressed pixel data.
+    pub data: Vec<u8>,
+}
+
+impl Canvas {
+    /// Decompresses the image and converts it to BGRA8 pixels, the format used by NX bitmaps.
+    pub fn pixels(&self) -> Result<Vec<u8>, Error> {
+        let unsupported = Error::UnsupportedCanvas { format: self.format, scale: self.scale };
+        if self.scale != 0 {
+            return Err(unsupported)
+        }
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&self.data[..]).read_to_end(&mut raw)?;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let size = width * height;
+        let mut out = vec![0; size * 4];
+        let short = Error::InvalidWz {
+            offset: 0,
+            version: None,
+            reason: "canvas data is too short",
+        };
+        match self.format {
+            1 => {
+                let raw = raw.get(..size * 2).ok_or(short)?;
+                for (pixel, p) in out.chunks_mut(4).zip(shorts(raw)) {
+                    for (i, byte) in pixel.iter_mut().enumerate() {
+                        *byte = ((p >> (i * 4)) & 0xF) as u8 * 0x11;
+                    }
+                }
+            },
+            2 => out.copy_from_slice(raw.get(..size * 4).ok_or(short)?),
+            257 => {
+                let raw = raw.get(..size * 2).ok_or(short)?;
+                for (pixel, p) in out.chunks_mut(4).zip(shorts(raw)) {
+                    pixel[0] = expand5(p);
+                    pixel[1] = expand5(p >> 5);
+                    pixel[2] = expand5(p >> 10);
+                    pixel[3] = if p & 0x8000 != 0 { 0xFF } else { 0 };
+                }
+            },
+            513 => {
+                let raw = raw.get(..size * 2).ok_or(short)?;
+                for (pixel, p) in out.chunks_mut(4).zip(shorts(raw)) {
+                    pixel.copy_from_slice(&rgb565(p));
+                }
+            },
+            517 => {
+                let stride = width.div_ceil(16);
+                let raw = raw.get(..stride * height.div_ceil(16) * 2).ok_or(short)?;
+                let blocks = shorts(raw).collect::<Vec<_>>();
+                for (i, pixel) in out.chunks_mut(4).enumerate() {
+                    let (x, y) = (i % width, i / width);
+                    pixel.copy_from_slice(&rgb565(blocks[y / 16 * stride + x / 16]));
+                }
+            },
+            1026 | 2050 => {
+                let stride = width.div_ceil(4);
+                let raw = raw.get(..stride * height.div_ceil(4) * 16).ok_or(short)?;
+                for (i, block) in raw.chunks(16).enumerate() {
+                    let pixels = if self.format == 1026 { dxt3(block) } else { dxt5(block) };
+                    let (bx, by) = (i % stride * 4, i / stride * 4);
+                    for (j, pixel) in pixels.iter().enumerate() {
+                        let (x, y) = (bx + j % 4, by + j / 4);
+                        if x < width && y < height {
+                            let at = (y * width + x) * 4;
+                            out[at..at + 4].copy_from_slice(pixel);
+                        }
+                    }
+                }
+            },
+            _ => return Err(unsupported),
+        }
+        Ok(out)
+    }
+}
+
+/// Iterates over the little endian `u16` in some bytes.
+fn shorts(bytes: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    bytes.chunks(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+}
+
+/// Expands the low five bits of a value to eight bits.
+fn expand5(value: u16) -> u8 {
+    let value = (value & 0x1F) as u8;
+    value << 3 | value >> 2
+}
+
+/// Converts an RGB565 color to BGRA8.
+fn rgb565(p: u16) -> [u8; 4] {
+    let g = ((p >> 5) & 0x3F) as u8;
+    [expand5(p), g << 2 | g >> 4, expand5(p >> 11), 0xFF]
+}
+
+/// Decodes the color part of a DXT block.
+fn dxt_colors(block: &[u8]) -> [[u8; 4]; 16] {
+    let c0 = rgb565(u16::from_le_bytes([block[0], block[1]]));
+    let c1 = rgb565(u16::from_le_bytes([block[2], block[3]]));
+    let mix = |a: u8, b: u8| ((2 * a as u16 + b as u16) / 3) as u8;
+    let mut palette = [c0, c1, [0; 4], [0; 4]];
+    for i in 0..3 {
+        palette[2][i] = mix(c0[i], c1[i]);
+        palette[3][i] = mix(c1[i], c0[i]);
+    }
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut pixels = [[0; 4]; 16];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[(indices >> (i * 2) & 3) as usize];
+    }
+    pixels
+}
+
+/// Decodes a DXT3 block into 4x4 BGRA8 pixels.
+fn dxt3(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut pixels = dxt_colors(&block[8..]);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = (block[i / 2] >> (i % 2 * 4) & 0xF) * 0x11;
+    }
+    pixels
+}
+
+/// Decodes a DXT5 block into 4x4 BGRA8 pixels.
+fn dxt5(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut pixels = dxt_colors(&block[8..]);
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut alphas = [a0, a1, 0, 0, 0, 0, 0, 0];
+    if a0 > a1 {
+        for i in 1..7 {
+            alphas[i + 1] = ((7 - i as u32) * a0 + i as u32 * a1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            alphas[i + 1] = ((5 - i as u32) * a0 + i as u32 * a1) / 5;
+        }
+        alphas[7] = 0xFF;
+    }
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = alphas[(indices >> (i * 3) & 7) as usize] as u8;
+    }
+    pixels
+}
+
+/// A sound stored in a sound property.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sound {
+    /// The length of the sound in milliseconds.
+    pub duration: i32,
+    /// The wz audio header, which describes the format of the sound.
+    pub header: Vec<u8>,
+    /// The sound data.
+    pub data: Vec<u8>,
+}
+
+impl Sound {
+    /// Gets the header followed by the data, which is how audio is stored in NX files.
+    pub fn to_audio(&self) -> Vec<u8> {
+        let mut audio = self.header.clone();
+        audio.extend_from_slice(&self.data);
+        audio
+    }
+}
+
+/// Reads a WZ archive from a file, working out its version from its header.
+pub fn read(path: &Path, encryption: Encryption) -> Result<Node, Error> {
+    parse(&fs::read(path)?, encryption, None)
+}
+
+/// Reads a WZ archive from memory. If `version` is `None`, it is worked out from the header.
+///
+/// The header only narrows the version down to one in 256, so each version which matches it is
+/// tried in turn. If none of them can read the archive, the error is the one from the first.
+pub fn parse(data: &[u8], encryption: Encryption, version: Option<u16>) -> Result<Node, Error> {
+    let mut reader = Reader::new(data, Key::new(encryption));
+    if reader.bytes(4)? != b"PKG1" {
+        return Err(reader.error("header magic value was invalid"))
+    }
+    reader.u64()?;
+    reader.start = reader.u32()?;
+    reader.seek(reader.start as usize)?;
+    let encrypted = reader.u16()?;
+    // Only versions whose hash matches the header are worth reading the whole archive for.
+    let candidates: Vec<u16> = match version {
+        Some(version) => vec![version],
+        None => (0..=u16::MAX)
+            .filter(|&candidate| encrypted_version(version_hash(candidate)) == encrypted)
+            .collect(),
+    };
+    let mut error = None;
+    for candidate in candidates {
+        reader.version = Some(candidate);
+        reader.hash = version_hash(candidate);
+        reader.visited.clear();
+        reader.seek(reader.start as usize + 2)?;
+        match reader.directory(String::new()) {
+            Ok(node) => return Ok(node),
+            Err(e) => { error.get_or_insert(e); },
+        }
+    }
+    reader.version = None;
+    Err(error.unwrap_or_else(|| reader.error("no version matches the header")))
+}
+
+/// Hashes a version number the way the offsets of an archive are encrypted with.
+fn version_hash(version: u16) -> u32 {
+    version.to_string().bytes().fold(0u32, |hash, c| {
+        hash.wrapping_mul(32).wrapping_add(c as u32).wrapping_add(1)
+    })
+}
+
+/// Gets the encrypted version stored in the header of an archive from the version hash.
+fn encrypted_version(hash: u32) -> u16 {
+    (0xFF ^ (hash >> 24) ^ (hash >> 16) ^ (hash >> 8) ^ hash) as u16 & 0xFF
+}
+
+/// Reads values from an archive.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    start: u32,
+    /// The version the archive is being read as, and the hash offsets are decrypted with.
+    version: Option<u16>,
+    hash: u32,
+    key: Key,
+    /// The offsets of the directories and images which have been read, so that an entry
+    /// pointing back at one of them is not read again.
+    visited: HashSet<usize>,
+    /// How deeply the property being read is nested.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], key: Key) -> Reader<'a> {
+        Reader {
+            data,
+            pos: 0,
+            start: 0,
+            version: None,
+            hash: 0,
+            key,
+            visited: HashSet::new(),
+            depth: 0,
+        }
+    }
+    fn error(&self, reason: &'static str) -> Error {
+        Error::InvalidWz { offset: self.pos as u64, version: self.version, reason }
+    }
+    fn seek(&mut self, pos: usize) -> Result<(), Error> {
+        if pos > self.data.len() {
+            return Err(self.error("offset is past the end of the archive"))
+        }
+        self.pos = pos;
+        Ok(())
+    }
+    fn skip(&mut self, len: usize) -> Result<(), Error> {
+        self.bytes(len).map(|_| ())
+    }
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        match self.data.get(self.pos..).and_then(|data| data.get(..len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            },
+            None => Err(self.error("unexpected end of the archive")),
+        }
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+    fn u16(&mut self) -> Result<u16, Error> {
+        self.array().map(u16::from_le_bytes)
+    }
+    fn i16(&mut self) -> Result<i16, Error> {
+        self.array().map(i16::from_le_bytes)
+    }
+    fn u32(&mut self) -> Result<u32, Error> {
+        self.array().map(u32::from_le_bytes)
+    }
+    fn i32(&mut self) -> Result<i32, Error> {
+        self.array().map(i32::from_le_bytes)
+    }
+    fn u64(&mut self) -> Result<u64, Error> {
+        self.array().map(u64::from_le_bytes)
+    }
+    fn i64(&mut self) -> Result<i64, Error> {
+        self.array().map(i64::from_le_bytes)
+    }
+    fn f32(&mut self) -> Result<f32, Error> {
+        self.array().map(f32::from_le_bytes)
+    }
+    fn f64(&mut self) -> Result<f64, Error> {
+        self.array().map(f64::from_le_bytes)
+    }
+    /// Reads an int stored in one byte, or in five if the first byte is -128.
+    fn compressed_i32(&mut self) -> Result<i32, Error> {
+        match self.u8()? as i8 {
+            -128 => self.i32(),
+            value => Ok(value as i32),
+        }
+    }
+    /// Reads a long stored in one byte, or in nine if the first byte is -128.
+    fn compressed_i64(&mut self) -> Result<i64, Error> {
+        match self.u8()? as i8 {
+            -128 => self.i64(),
+            value => Ok(value as i64),
+        }
+    }
+    /// Reads a length which must not be negative.
+    fn len(&mut self) -> Result<usize, Error> {
+        match self.compressed_i32()? {
+            len if len < 0 => Err(self.error("length is negative")),
+            len => Ok(len as usize),
+        }
+    }
+    /// Reads an encrypted string.
+    fn string(&mut self) -> Result<String, Error> {
+        let small = self.u8()? as i8;
+        if small > 0 {
+            let len = match small {
+                127 => self.i32()?,
+                len => len as i32,
+            };
+            if len < 0 {
+                return Err(self.error("length is negative"))
+            }
+            let bytes = self.bytes(len as usize * 2)?;
+            let key = self.key.get(bytes.len());
+            let units = shorts(bytes).zip(key.chunks(2)).enumerate().map(|(i, (unit, key))| {
+                unit ^ 0xAAAAu16.wrapping_add(i as u16) ^ u16::from_le_bytes([key[0], key[1]])
+            }).collect::<Vec<_>>();
+            String::from_utf16(&units).map_err(|_| self.error("string is not valid UTF-16"))
+        } else if small < 0 {
+            let len = match small {
+                -128 => self.i32()?,
+                len => -(len as i32),
+            };
+            if len < 0 {
+                return Err(self.error("length is negative"))
+            }
+            let bytes = self.bytes(len as usize)?;
+            let key = self.key.get(bytes.len());
+            Ok(bytes.iter().zip(key).enumerate().map(|(i, (&byte, &key))| {
+                (byte ^ 0xAAu8.wrapping_add(i as u8) ^ key) as char
+            }).collect())
+        } else {
+            Ok(String::new())
+        }
+    }
+    /// Reads an encrypted string at an offset, without moving.
+    fn string_at(&mut self, offset: usize) -> Result<String, Error> {
+        let pos = self.pos;
+        self.seek(offset)?;
+        let string = self.string();
+        self.pos = pos;
+        string
+    }
+    /// Reads a string in an image, which is either stored in place or at an offset from the
+    /// start of the image.
+    fn string_block(&mut self, image: usize) -> Result<String, Error> {
+        match self.u8()? {
+            0x00 | 0x73 => self.string(),
+            0x01 | 0x1B => {
+                let offset = self.i32()?;
+                self.string_at(image.wrapping_add(offset as usize))
+            },
+            _ => Err(self.error("unknown string type")),
+        }
+    }
+    /// Reads an encrypted offset from the start of the archive.
+    fn offset(&mut self) -> Result<usize, Error> {
+        let mut offset = (self.pos as u32).wrapping_sub(self.start) ^ 0xFFFFFFFF;
+        offset = offset.wrapping_mul(self.hash).wrapping_sub(OFFSET_KEY);
+        offset = offset.rotate_left(offset & 0x1F);
+        offset ^= self.u32()?;
+        Ok(offset.wrapping_add(self.start.wrapping_mul(2)) as usize)
+    }
+    /// Reads a directory and everything in it.
+    fn directory(&mut self, name: String) -> Result<Node, Error> {
+        let mut entries = Vec::new();
+        for _ in 0..self.len()? {
+            let (kind, name) = match self.u8()? {
+                1 => {
+                    self.skip(6)?;
+                    self.offset()?;
+                    continue
+                },
+                2 => {
+                    let offset = self.i32()?;
+                    let pos = self.pos;
+                    self.seek((self.start as usize).wrapping_add(offset as usize))?;
+                    let entry = (self.u8()?, self.string()?);
+                    self.pos = pos;
+                    entry
+                },
+                kind @ 3 | kind @ 4 => (kind, self.string()?),
+                _ => return Err(self.error("unknown directory entry type")),
+            };
+            self.compressed_i32()?;
+            self.compressed_i32()?;
+            let offset = self.offset()?;
+            if offset >= self.data.len() {
+                return Err(self.error("directory entry is past the end of the archive"))
+            }
+            if !self.visited.insert(offset) {
+                return Err(self.error("directory entry points at an entry already read"))
+            }
+            entries.push((kind, name, offset));
+        }
+        let mut node = Node::new(name, Value::Empty);
+        for (kind, name, offset) in entries {
+            self.seek(offset)?;
+            node.children.push(match kind {
+                3 => self.directory(name)?,
+                4 => self.image(name)?,
+                _ => return Err(self.error("unknown directory entry type")),
+            });
+        }
+        Ok(node)
+    }
+    /// Reads an image and all of its properties.
+    fn image(&mut self, name: String) -> Result<Node, Error> {
+        let image = self.pos;
+        if self.u8()? != 0x73 || self.string()? != "Property" || self.u16()? != 0 {
+            return Err(self.error("image header was invalid"))
+        }
+        let mut node = Node::new(name, Value::Empty);
+        node.children = self.properties(image)?;
+        Ok(node)
+    }
+    /// Reads a list of properties.
+    fn properties(&mut self, image: usize) -> Result<Vec<Node>, Error> {
+        let count = self.len()?;
+        let mut nodes = Vec::new();
+        for _ in 0..count {
+            let name = self.string_block(image)?;
+            let value = match self.u8()? {
+                0 => Value::Empty,
+                2 | 11 => Value::Integer(self.i16()? as i64),
+                3 | 19 => Value::Integer(self.compressed_i32()? as i64),
+                20 => Value::Integer(self.compressed_i64()?),
+                4 => Value::Float(match self.u8()? {
+                    0x80 => self.f32()? as f64,
+                    _ => 0.,
+                }),
+                5 => Value::Float(self.f64()?),
+                8 => Value::String(self.string_block(image)?),
+                9 => {
+                    let size = self.u32()? as usize;
+                    let end = self.pos + size;
+                    let node = self.extended(image, name)?;
+                    self.seek(end)?;
+                    nodes.push(node);
+                    continue
+                },
+                _ => return Err(self.error("unknown property type")),
+            };
+            nodes.push(Node::new(name, value));
+        }
+        Ok(nodes)
+    }
+    /// Reads a property which is an object rather than a plain value.
+    fn extended(&mut self, image: usize, name: String) -> Result<Node, Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("properties are nested too deeply"))
+        }
+        self.depth += 1;
+        let node = self.extended_value(image, name);
+        self.depth -= 1;
+        node
+    }
+    /// Reads the type and contents of a property which is an object.
+    fn extended_value(&mut self, image: usize, name: String) -> Result<Node, Error> {
+        let kind = self.string_block(image)?;
+        let mut node = Node::new(name, Value::Empty);
+        match &*kind {
+            "Property" => {
+                self.skip(2)?;
+                node.children = self.properties(image)?;
+            },
+            "Canvas" => {
+                self.skip(1)?;
+                if self.u8()? == 1 {
+                    self.skip(2)?;
+                    node.children = self.properties(image)?;
+                }
+                node.value = Value::Canvas(self.canvas()?);
+            },
+            "Shape2D#Vector2D" => {
+                node.value = Value::Vector(self.compressed_i32()?, self.compressed_i32()?);
+            },
+            "Shape2D#Convex2D" => {
+                for i in 0..self.len()? {
+                    node.children.push(self.extended(image, i.to_string())?);
+                }
+            },
+            "Sound_DX8" => node.value = Value::Sound(self.sound()?),
+            "UOL" => {
+                self.skip(1)?;
+                node.value = Value::Uol(match self.u8()? {
+                    0 => self.string()?,
+                    1 => {
+                        let offset = self.i32()?;
+                        self.string_at(image.wrapping_add(offset as usize))?
+                    },
+                    _ => return Err(self.error("unknown string type")),
+                });
+            },
+            _ => return Err(self.error("unknown extended property type")),
+        }
+        Ok(node)
+    }
+    /// Reads the image of a canvas, decrypting it if needed.
+    fn canvas(&mut self) -> Result<Canvas, Error> {
+        let width = self.compressed_i32()?;
+        let height = self.compressed_i32()?;
+        if width < 0 || width > u16::MAX as i32 || height < 0 || height > u16::MAX as i32 {
+            return Err(self.error("canvas size is out of range"))
+        }
+        let format = self.compressed_i32()? as u32;
+        let scale = self.u8()?;
+        self.skip(4)?;
+        let len = self.i32()?;
+        if len < 1 {
+            return Err(self.error("length is negative"))
+        }
+        self.skip(1)?;
+        let raw = self.bytes(len as usize - 1)?;
+        let data = match raw.get(..2) {
+            Some(&[0x78, 0x9C]) | Some(&[0x78, 0xDA]) | Some(&[0x78, 0x01])
+                | Some(&[0x78, 0x5E]) => raw.to_vec(),
+            _ => {
+                let mut data = Vec::new();
+                let mut blocks = Reader::new(raw, Key::new(Encryption::None));
+                while blocks.pos < raw.len() {
+                    let size = blocks.i32()?;
+                    if size < 0 {
+                        return Err(self.error("length is negative"))
+                    }
+                    let block = blocks.bytes(size as usize)?;
+                    let key = self.key.get(block.len());
+                    data.extend(block.iter().zip(key).map(|(&byte, &key)| byte ^ key));
+                }
+                data
+            },
+        };
+        Ok(Canvas { width: width as u16, height: height as u16, format, scale, data })
+    }
+    /// Reads a sound and its header.
+    fn sound(&mut self) -> Result<Sound, Error> {
+        self.skip(1)?;
+        let len = self.len()?;
+        let duration = self.compressed_i32()?;
+        let header = self.pos;
+        self.skip(51)?;
+        let format = self.u8()? as usize;
+        self.pos = header;
+        let header = self.bytes(52 + format)?.to_vec();
+        let data = self.bytes(len)?.to_vec();
+        Ok(Sound { duration, header, data })
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Owned nodes backed by an `Arc<File>`
+extern crate nx;
+
+use std::sync::Arc;
+use std::thread;
+
+use nx::write::{Builder, NodeValue};
+use nx::{ArcNode, NodeId};
+
+fn file() -> Arc<nx::File> {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let mob = builder.add(root, "Mob", NodeValue::Empty);
+    let info = builder.add(mob, "info", NodeValue::Empty);
+    builder.add(info, "level", NodeValue::Integer(10));
+    builder.add(info, "name", NodeValue::String("Snail".into()));
+    Arc::new(nx::File::from_bytes(builder.to_vec().unwrap()).unwrap())
+}
+
+#[test]
+fn round_trip() {
+    let file = file();
+    let level = ArcNode::root(file.clone()).resolve("Mob/info/level").unwrap();
+    assert_eq!(level.integer(), Some(10));
+    assert_eq!(level.path(), "Mob/info/level");
+    let node = level.node();
+    assert!(ArcNode::from_node(&file, node) == level);
+    assert!(ArcNode::new(file.clone(), level.id()) == Some(level.clone()));
+    assert!(level.parent().and_then(|info| info.get("level")) == Some(level.clone()));
+    assert!(ArcNode::new(file.clone(), NodeId(file.node_count())).is_none());
+    let info = ArcNode::root(file.clone()).get("Mob").unwrap().get("info").unwrap();
+    assert_eq!(info.iter().filter(|child| *child == level).count(), 1);
+}
+
+#[test]
+fn other_file() {
+    let (first, second) = (file(), file());
+    let level = ArcNode::root(first).resolve("Mob/info/level").unwrap();
+    let other = ArcNode::root(second).resolve("Mob/info/level").unwrap();
+    assert_eq!(level.id(), other.id());
+    assert!(level != other);
+}
+
+#[test]
+fn threads() {
+    let file = file();
+    let info = ArcNode::root(file.clone()).resolve("Mob/info").unwrap();
+    let name = {
+        let info = info.clone();
+        thread::spawn(move || info.get("name").unwrap()).join().unwrap()
+    };
+    drop(file);
+    assert_eq!(name.string(), Some("Snail"));
+    assert!(name.parent() == Some(info));
+}
@@ -0,0 +1,40 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Extracting bitmaps and audio into a directory tree
+#![cfg(feature = "extract")]
+extern crate nx;
+
+use std::fs;
+
+use nx::bitmap::Compression;
+use nx::extract::{ExtractOptions, MANIFEST};
+use nx::write::{Builder, NodeValue};
+
+#[test]
+fn clashing_names() {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let image = builder.add(root, "Mob.img", NodeValue::Empty);
+    for name in &["a*", "a?", "A_", "a_~1", "tab\there"] {
+        builder.add(image, *name, NodeValue::bitmap(&[0; 4], 1, 1, Compression::Fast));
+    }
+    let file = nx::File::from_bytes(builder.to_vec().unwrap()).unwrap();
+    let dir = std::env::temp_dir().join(format!("nx-extract-{}", std::process::id()));
+    let manifest = ExtractOptions::new().threads(4).extract(file.root(), &dir);
+    let files = manifest.as_ref().map(|manifest| {
+        manifest.entries.iter().map(|entry| entry.file.clone()).collect::<Vec<_>>()
+    });
+    let written = fs::read_dir(dir.join("Mob.img")).map(|entries| entries.count());
+    let tsv = fs::read_to_string(dir.join(MANIFEST));
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Children are sorted by bytes, so `A_` comes first and keeps its name.
+    let files = files.unwrap();
+    let expected = ["A_", "a_~1", "a_~2", "a_~1~1", "tab_here"].iter()
+        .map(|name| ["Mob.img", &format!("{}.png", name)].iter().collect())
+        .collect::<Vec<std::path::PathBuf>>();
+    assert_eq!(files, expected);
+    assert_eq!(written.unwrap(), 5);
+    let tsv = tsv.unwrap();
+    assert_eq!(tsv.lines().count(), 6);
+    assert!(tsv.contains("\tMob.img/tab\\there\t"), "{}", tsv);
+}
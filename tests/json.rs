@@ -0,0 +1,129 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Exporting to JSON and importing the result again
+#![cfg(feature = "json")]
+extern crate nx;
+
+use nx::bitmap::Compression;
+use nx::export::json;
+use nx::import::json as import;
+use nx::import::ImportOptions;
+use nx::write::{Builder, NodeValue};
+use nx::{Error, GenericNode, Type};
+
+/// Builds a file with nodes whose JSON could be mistaken for one another.
+fn clashing() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let pos = builder.add(root, "pos", NodeValue::Empty);
+    builder.add(pos, "x", NodeValue::Integer(3));
+    builder.add(pos, "y", NodeValue::Integer(4));
+    builder.add(root, "vector", NodeValue::Vector(3, 4));
+    let bitmap = builder.add(root, "bitmap", NodeValue::Empty);
+    builder.add(bitmap, "type", NodeValue::String("bitmap".into()));
+    builder.add(bitmap, "width", NodeValue::Integer(1));
+    builder.add(bitmap, "height", NodeValue::Integer(1));
+    builder.add(bitmap, "length", NodeValue::Integer(4));
+    let tagged = builder.add(root, "tagged", NodeValue::Empty);
+    builder.add(tagged, "type", NodeValue::String("vector".into()));
+    builder.add(tagged, "x", NodeValue::Integer(1));
+    builder.add(tagged, "y", NodeValue::Integer(2));
+    let audio = builder.add(root, "audio", NodeValue::Vector(5, 6));
+    builder.add(audio, "type", NodeValue::String("audio".into()));
+    let dollar = builder.add(root, "dollar", NodeValue::Empty);
+    builder.add(dollar, "$value", NodeValue::Integer(5));
+    builder.add(dollar, "$$value", NodeValue::String("two".into()));
+    builder.add(dollar, "$other", NodeValue::String("one".into()));
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+#[test]
+fn clashing_round_trip() {
+    let file = clashing();
+    let exported = json::to_string(file.root()).unwrap();
+    let imported = import::from_str(&exported).unwrap();
+    let file = nx::File::from_bytes(imported.to_vec().unwrap()).unwrap();
+    assert_eq!(json::to_string(file.root()).unwrap(), exported);
+
+    let root = file.root();
+    let pos = root.get("pos").unwrap();
+    assert_eq!(pos.dtype(), Type::Empty);
+    assert_eq!(pos.get("x").and_then(|x| x.integer()), Some(3));
+    assert_eq!(pos.get("y").and_then(|y| y.integer()), Some(4));
+    assert_eq!(root.get("vector").and_then(|v| v.vector()), Some((3, 4)));
+    let bitmap = root.get("bitmap").unwrap();
+    assert_eq!(bitmap.dtype(), Type::Empty);
+    assert_eq!(bitmap.get("type").and_then(|t| t.string()), Some("bitmap"));
+    let tagged = root.get("tagged").unwrap();
+    assert_eq!(tagged.dtype(), Type::Empty);
+    assert_eq!(tagged.get("type").and_then(|t| t.string()), Some("vector"));
+    let audio = root.get("audio").unwrap();
+    assert_eq!(audio.vector(), Some((5, 6)));
+    assert_eq!(audio.get("type").and_then(|t| t.string()), Some("audio"));
+    let dollar = root.get("dollar").unwrap();
+    assert_eq!(dollar.dtype(), Type::Empty);
+    assert_eq!(dollar.get("$value").and_then(|v| v.integer()), Some(5));
+    assert_eq!(dollar.get("$$value").and_then(|v| v.string()), Some("two"));
+    assert_eq!(dollar.get("$other").and_then(|v| v.string()), Some("one"));
+}
+
+/// Builds a file with a bitmap and audio, compressed with a level which importing with the
+/// default options would not use, so copying them can be told apart from recompressing them.
+fn media() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let pixels = [0x10, 0x20, 0x30, 0xFF].repeat(6);
+    let images = builder.add(root, "images", NodeValue::Empty);
+    builder.add(images, "bitmap", NodeValue::bitmap(&pixels, 3, 2, Compression::High(12)));
+    let mut audio = vec![0; 82];
+    audio.extend_from_slice(b"some audio data");
+    builder.add(root, "sound", NodeValue::Audio(audio));
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+fn open(builder: Builder) -> nx::File {
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+#[test]
+fn copy_from_base() {
+    let base = media();
+    let exported = json::to_string(base.root()).unwrap();
+    assert!(!exported.contains("\"data\""));
+    match import::from_str(&exported) {
+        Err(Error::InvalidJson { path, .. }) => assert_eq!(path, "images/bitmap"),
+        result => panic!("expected the import to need a base, got {:?}", result.err()),
+    }
+    let imported = ImportOptions::new().base(base.root()).read_json_str(&exported).unwrap();
+    let file = open(imported);
+    let bitmap = file.root().resolve("images/bitmap").and_then(|node| node.bitmap()).unwrap();
+    let original = base.root().resolve("images/bitmap").and_then(|node| node.bitmap()).unwrap();
+    assert_eq!(bitmap.raw(), original.raw());
+    let audio = file.root().get("sound").and_then(|node| node.audio()).unwrap();
+    let original = base.root().get("sound").and_then(|node| node.audio()).unwrap();
+    assert_eq!(audio.raw(), original.raw());
+}
+
+#[test]
+fn source() {
+    let base = media();
+    let json = r#"{"icon": {"type": "bitmap", "width": 3, "height": 2, "source": "images/bitmap"},
+        "bgm": {"type": "audio", "source": "sound"}}"#;
+    let file = open(ImportOptions::new().base(base.root()).read_json_str(json).unwrap());
+    let original = base.root().resolve("images/bitmap").and_then(|node| node.bitmap());
+    assert_eq!(file.root().get("icon").and_then(|node| node.bitmap()), original);
+    let original = base.root().get("sound").and_then(|node| node.audio());
+    assert_eq!(file.root().get("bgm").and_then(|node| node.audio()), original);
+}
+
+#[test]
+fn missing_source() {
+    let base = media();
+    let json = r#"{"images": {"bitmap": {"type": "bitmap", "width": 3, "height": 2},
+        "other": {"type": "bitmap", "width": 3, "height": 2}}}"#;
+    match ImportOptions::new().base(base.root()).read_json_str(json) {
+        Err(Error::NotFound { path, segment }) => {
+            assert_eq!((&*path, &*segment), ("images/other", "other"))
+        },
+        result => panic!("expected the source to be missing, got {:?}", result.err()),
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Following links between nodes
+extern crate nx;
+
+use nx::bitmap::Compression;
+use nx::link::Link;
+use nx::write::{Builder, NodeValue};
+use nx::{Error, GenericNode, Node};
+
+/// Builds a file with a link of each kind inside `Map.img`, most of them pointing at its
+/// `back/0` bitmap.
+fn file() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let image = builder.add(root, "Map.img", NodeValue::Empty);
+    let back = builder.add(image, "back", NodeValue::Empty);
+    builder.add(back, "0", NodeValue::bitmap(&[1, 2, 3, 4], 1, 1, Compression::Fast));
+    let inlink = builder.add(back, "1", NodeValue::Empty);
+    builder.add(inlink, "_inlink", NodeValue::String("back/0".into()));
+    let outlink = builder.add(image, "outlink", NodeValue::Empty);
+    builder.add(outlink, "_outlink", NodeValue::String("Map.img/back/1".into()));
+    let prefixed = builder.add(image, "prefixed", NodeValue::Empty);
+    builder.add(prefixed, "_outlink", NodeValue::String("Map/Map.img/back/0".into()));
+    let life = builder.add(image, "life", NodeValue::Empty);
+    builder.add(life, "uol", NodeValue::String("../back/1".into()));
+    builder.add(life, "sibling", NodeValue::String("uol".into()));
+    for &(name, target) in &[("a", "b"), ("b", "a")] {
+        let cycle = builder.add(image, name, NodeValue::Empty);
+        builder.add(cycle, "_inlink", NodeValue::String(target.into()));
+    }
+    let dangling = builder.add(image, "dangling", NodeValue::Empty);
+    builder.add(dangling, "_outlink", NodeValue::String("Map/Map.img/missing".into()));
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+fn node<'a>(file: &'a nx::File, path: &str) -> Node<'a> {
+    file.root().resolve(path).unwrap()
+}
+
+#[test]
+fn links() {
+    let file = file();
+    assert_eq!(node(&file, "Map.img/back/1").link(), Some(Link::Inlink("back/0")));
+    assert_eq!(node(&file, "Map.img/outlink").link(), Some(Link::Outlink("Map.img/back/1")));
+    assert_eq!(node(&file, "Map.img/life/uol").link(), Some(Link::Uol("../back/1")));
+    assert_eq!(node(&file, "Map.img/life/sibling").link(), None);
+    assert_eq!(node(&file, "Map.img/back/0").link(), None);
+}
+
+#[test]
+fn follow() {
+    let file = file();
+    let target = node(&file, "Map.img/back/0");
+    assert_eq!(target.follow().unwrap().id(), target.id());
+    for path in &["back/1", "outlink", "prefixed", "life/uol"] {
+        let link = node(&file, "Map.img").resolve(path).unwrap();
+        assert_eq!(link.follow().unwrap().id(), target.id(), "{} was not followed", path);
+        let bitmap = link.bitmap_resolved().unwrap().unwrap();
+        assert_eq!(bitmap, target.bitmap().unwrap());
+    }
+    // Strings which do not start with `../` are never followed, even if they are UOLs.
+    let sibling = node(&file, "Map.img/life/sibling");
+    assert_eq!(sibling.follow().unwrap().id(), sibling.id());
+}
+
+#[test]
+fn cycle() {
+    let file = file();
+    match node(&file, "Map.img/a").follow() {
+        Err(Error::LinkCycle { path }) => assert_eq!(path, "Map.img/a"),
+        result => panic!("expected a cycle, got {:?}", result.map(|node| node.path())),
+    }
+}
+
+#[test]
+fn dangling() {
+    let file = file();
+    match node(&file, "Map.img/dangling").follow() {
+        Err(Error::DanglingLink { path, target }) => {
+            assert_eq!((&*path, &*target), ("Map.img/dangling", "Map/Map.img/missing"))
+        },
+        result => panic!("expected a dangling link, got {:?}", result.map(|node| node.path())),
+    }
+}
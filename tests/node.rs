@@ -0,0 +1,209 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Reading nodes of a small file
+extern crate nx;
+
+use nx::audio::Audio;
+use nx::bitmap::{Bitmap, Compression};
+use nx::write::{Builder, NodeValue};
+use nx::{Error, GenericNode, Node, Type};
+
+fn open(builder: Builder) -> nx::File {
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+/// Builds a file with the children of the root named after their values.
+fn coercions() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let values = vec![
+        ("0", NodeValue::Integer(0)),
+        ("1", NodeValue::Integer(1)),
+        ("2", NodeValue::Integer(2)),
+        ("-1", NodeValue::Integer(-1)),
+        ("65536", NodeValue::Integer(65536)),
+        ("2^40", NodeValue::Integer(1 << 40)),
+        ("1.0", NodeValue::Float(1.0)),
+        ("-2.75", NodeValue::Float(-2.75)),
+        ("1e300", NodeValue::Float(1e300)),
+        ("nan", NodeValue::Float(f64::NAN)),
+        ("\" 120 \"", NodeValue::String(" 120 ".into())),
+        ("\"7.9\"", NodeValue::String("7.9".into())),
+        ("\"TRUE\"", NodeValue::String("TRUE".into())),
+        ("\"false\"", NodeValue::String("false".into())),
+        ("\"abc\"", NodeValue::String("abc".into())),
+        ("(3, -4)", NodeValue::Vector(3, -4)),
+        ("empty", NodeValue::Empty),
+    ];
+    for (name, value) in values {
+        builder.add(root, name, value);
+    }
+    open(builder)
+}
+
+#[test]
+fn integers() {
+    let file = coercions();
+    let node = |name| file.root().get(name).unwrap();
+    assert_eq!(node("2^40").as_i64(), Some(1 << 40));
+    assert_eq!(node("2^40").as_i32(), None);
+    assert_eq!(node("65536").as_i32(), Some(65536));
+    assert_eq!(node("65536").as_u16(), None);
+    assert_eq!(node("-1").as_u16(), None);
+    assert_eq!(node("-2.75").as_i64(), Some(-2));
+    assert_eq!(node("1e300").as_i64(), None);
+    assert_eq!(node("nan").as_i64(), None);
+    assert_eq!(node("\" 120 \"").as_u16(), Some(120));
+    assert_eq!(node("\"7.9\"").as_i32(), Some(7));
+    assert_eq!(node("\"abc\"").as_i64(), None);
+    assert_eq!(node("(3, -4)").as_i64(), None);
+    assert_eq!(node("empty").as_i64(), None);
+}
+
+#[test]
+fn floats() {
+    let file = coercions();
+    let node = |name| file.root().get(name).unwrap();
+    assert_eq!(node("2^40").as_f64(), Some((1u64 << 40) as f64));
+    assert_eq!(node("-2.75").as_f64(), Some(-2.75));
+    assert_eq!(node("\"7.9\"").as_f64(), Some(7.9));
+    assert_eq!(node("\" 120 \"").as_f64(), Some(120.));
+    assert_eq!(node("\"abc\"").as_f64(), None);
+    assert_eq!(node("empty").as_f64(), None);
+}
+
+#[test]
+fn booleans() {
+    let file = coercions();
+    let node = |name| file.root().get(name).unwrap();
+    assert_eq!(node("0").as_bool(), Some(false));
+    assert_eq!(node("1").as_bool(), Some(true));
+    assert_eq!(node("1.0").as_bool(), Some(true));
+    assert_eq!(node("\"TRUE\"").as_bool(), Some(true));
+    assert_eq!(node("\"false\"").as_bool(), Some(false));
+    for name in &["2", "-1", "-2.75", "nan", "\" 120 \"", "\"abc\"", "(3, -4)", "empty"] {
+        assert_eq!(node(name).as_bool(), None, "{} is not a boolean", name);
+    }
+}
+
+#[test]
+fn strings() {
+    let file = coercions();
+    let node = |name| file.root().get(name).unwrap();
+    assert_eq!(node("\"abc\"").to_string_lossy().as_deref(), Some("abc"));
+    assert_eq!(node("-1").to_string_lossy().as_deref(), Some("-1"));
+    assert_eq!(node("-2.75").to_string_lossy().as_deref(), Some("-2.75"));
+    assert_eq!(node("(3, -4)").to_string_lossy().as_deref(), Some("3,-4"));
+    assert_eq!(node("empty").to_string_lossy(), None);
+}
+
+/// A node which only implements the required methods, to check the provided ones.
+struct Wrapper<'a>(Node<'a>);
+
+impl<'a> GenericNode<'a> for Wrapper<'a> {
+    fn get(&self, name: &str) -> Option<Node<'a>> { self.0.get(name) }
+    fn dtype(&self) -> Type { self.0.dtype() }
+    fn string(&self) -> Option<&'a str> { self.0.string() }
+    fn integer(&self) -> Option<i64> { self.0.integer() }
+    fn float(&self) -> Option<f64> { self.0.float() }
+    fn vector(&self) -> Option<(i32, i32)> { self.0.vector() }
+    fn audio(&self) -> Option<Audio<'a>> { self.0.audio() }
+    fn bitmap(&self) -> Option<Bitmap<'a>> { self.0.bitmap() }
+}
+
+#[test]
+fn default_resolve() {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let mob = builder.add(root, "Mob", NodeValue::Empty);
+    let stand = builder.add(mob, "stand", NodeValue::Empty);
+    builder.add(stand, "0", NodeValue::Integer(120));
+    builder.add(mob, "speed", NodeValue::Integer(-40));
+    let file = open(builder);
+    let wrapper = Wrapper(file.root());
+    let expected = file.root().resolve("Mob/stand/0").unwrap();
+    assert_eq!(wrapper.resolve("Mob/stand/0").map(|node| node.id()), Some(expected.id()));
+    assert_eq!(wrapper.resolve("./Mob/stand/../speed").and_then(|node| node.integer()), Some(-40));
+    let mob = file.root().get("Mob").unwrap();
+    assert_eq!(wrapper.resolve("Mob").map(|node| node.id()), Some(mob.id()));
+    assert!(wrapper.resolve("").is_none());
+    assert!(wrapper.resolve("../Mob").is_none());
+    let not_found = |path, segment: &str| match wrapper.try_resolve(path) {
+        Err(Error::NotFound { path: ref found, segment: ref missing }) => {
+            assert_eq!(found, path);
+            assert_eq!(missing, segment);
+        },
+        _ => panic!("{} should not resolve", path),
+    };
+    not_found("Map/stand", "Map");
+    not_found("Mob/stand/1", "1");
+    not_found("../Mob", "..");
+}
+
+#[test]
+fn parents() {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let map = builder.add(root, "Map", NodeValue::Empty);
+    let image = builder.add(map, "Map.img", NodeValue::Empty);
+    let back = builder.add(image, "back", NodeValue::Empty);
+    builder.add(back, "0", NodeValue::Integer(0));
+    builder.add(root, "Mob", NodeValue::Empty);
+    let file = open(builder);
+    let root = file.root();
+    assert!(root.parent().is_none());
+    assert_eq!(root.ancestors().count(), 0);
+    assert_eq!(root.depth(), 0);
+    assert_eq!(root.path(), "");
+    let node = root.resolve("Map/Map.img/back/0").unwrap();
+    assert_eq!(node.path(), "Map/Map.img/back/0");
+    assert_eq!(node.depth(), 4);
+    let names: Vec<_> = node.ancestors().map(|node| node.name()).collect();
+    assert_eq!(names, ["back", "Map.img", "Map", ""]);
+    assert_eq!(node.ancestors().last().map(|node| node.id()), Some(root.id()));
+    let back = node.parent().unwrap();
+    assert_eq!(back.id(), root.resolve("Map/Map.img/back").unwrap().id());
+    assert_eq!(root.get("Mob").unwrap().parent().map(|node| node.id()), Some(root.id()));
+    assert_eq!(back.resolve("../../../Mob").unwrap().path(), "Mob");
+    assert!(root.resolve("..").is_none());
+}
+
+#[test]
+fn values() {
+    let mut audio = vec![0; 82];
+    audio[51] = 30;
+    audio.extend_from_slice(b"sound");
+    let mut builder = Builder::new();
+    let root = builder.root();
+    builder.add(root, "audio", NodeValue::Audio(audio));
+    builder.add(root, "bitmap", NodeValue::bitmap(&[0; 16], 2, 2, Compression::Fast));
+    builder.add(root, "empty", NodeValue::Empty);
+    builder.add(root, "float", NodeValue::Float(0.5));
+    builder.add(root, "integer", NodeValue::Integer(-7));
+    builder.add(root, "string", NodeValue::String("text".into()));
+    builder.add(root, "unknown", NodeValue::Unknown(9, *b"raw data"));
+    builder.add(root, "vector", NodeValue::Vector(3, -4));
+    let data = builder.to_vec().unwrap();
+    let file = nx::OpenOptions::new().allow_unknown_types(true).open_bytes(data).unwrap();
+    let value = |name| file.root().get(name).unwrap().value();
+    match value("audio") {
+        nx::Value::Audio(audio) => assert_eq!(audio.data(), b"sound"),
+        _ => panic!("expected audio"),
+    }
+    match value("bitmap") {
+        nx::Value::Bitmap(bitmap) => assert_eq!((bitmap.width(), bitmap.height()), (2, 2)),
+        _ => panic!("expected a bitmap"),
+    }
+    assert_eq!(value("empty"), nx::Value::Empty);
+    assert_eq!(value("float"), nx::Value::Float(0.5));
+    assert_eq!(value("integer"), nx::Value::Integer(-7));
+    assert_eq!(value("string"), nx::Value::String("text"));
+    assert_eq!(value("unknown"), nx::Value::Unknown(9, *b"raw data"));
+    assert_eq!(value("vector"), nx::Value::Vector(3, -4));
+    for node in file.root().iter() {
+        assert_eq!(node.value().dtype(), node.dtype());
+    }
+    assert_eq!(value("vector").to_string(), "(3, -4)");
+    assert_eq!(value("bitmap").to_string(), "2x2 bitmap");
+    assert_eq!(value("audio").to_string(), "5 bytes of audio");
+    assert_eq!(value("unknown").to_string(), "type 9 7261772064617461");
+}
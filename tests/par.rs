@@ -0,0 +1,56 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Visiting nodes in parallel
+#![cfg(feature = "rayon")]
+extern crate nx;
+extern crate rayon;
+
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use nx::write::{Builder, NodeValue};
+use nx::{GenericNode, Node, NodeId};
+
+/// Builds a file with an uneven tree, most of it under one child, so work has to be handed off.
+fn file() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let big = builder.add(root, "big", NodeValue::Empty);
+    for i in 0..50 {
+        let child = builder.add(big, i.to_string(), NodeValue::Empty);
+        for j in 0..20 {
+            builder.add(child, j.to_string(), NodeValue::Integer(i * 20 + j));
+        }
+    }
+    for name in &["a", "b", "c"] {
+        builder.add(root, *name, NodeValue::String((*name).into()));
+    }
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+fn ids<'a, I: Iterator<Item = Node<'a>>>(nodes: I) -> Vec<NodeId> {
+    let mut ids: Vec<_> = nodes.map(|node| node.id()).collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn par_iter() {
+    let file = file();
+    for node in file.root().descendants().chain(Some(file.root())) {
+        assert_eq!(node.par_iter().len(), node.iter().len());
+        let found: Vec<_> = node.par_iter().map(|node| node.id()).collect();
+        let expected: Vec<_> = node.iter().map(|node| node.id()).collect();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn par_descendants() {
+    let file = file();
+    for &path in &["", "big", "big/7", "big/7/3", "a"] {
+        let node = file.root().resolve(path).unwrap();
+        let mut found: Vec<_> = node.par_descendants().map(|node| node.id()).collect();
+        found.sort();
+        assert_eq!(found, ids(node.descendants()), "{}", path);
+    }
+    assert_eq!(file.root().par_descendants().count(), 1 + 50 + 50 * 20 + 3);
+}
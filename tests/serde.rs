@@ -0,0 +1,159 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Serializing typed values into nodes and deserializing them again
+#![cfg(feature = "serde")]
+extern crate nx;
+#[macro_use]
+extern crate serde_derive;
+
+use nx::de::from_node;
+use nx::ser::to_builder;
+use nx::write::{Builder, NodeValue};
+use nx::{Error, GenericNode, Type};
+
+#[derive(Deserialize, Debug, PartialEq)]
+enum Element {
+    Fire,
+    Ice,
+    Poison { damage: i32 },
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Mob<'a> {
+    name: &'a str,
+    level: u8,
+    speed: Option<i32>,
+    boss: Option<bool>,
+    origin: (i32, i32),
+    skills: Vec<u32>,
+    element: Element,
+    weakness: Element,
+}
+
+fn open(builder: Builder) -> nx::File {
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+fn mob() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let mob = builder.add(root, "mob", NodeValue::Empty);
+    builder.add(mob, "name", NodeValue::String("Snail".into()));
+    builder.add(mob, "level", NodeValue::Integer(1));
+    builder.add(mob, "boss", NodeValue::Integer(0));
+    builder.add(mob, "origin", NodeValue::Vector(-3, 7));
+    builder.add(mob, "unused", NodeValue::Float(0.5));
+    let skills = builder.add(mob, "skills", NodeValue::Empty);
+    for (index, skill) in [100, 200, 300].iter().enumerate() {
+        builder.add(skills, index.to_string(), NodeValue::Integer(*skill));
+    }
+    builder.add(mob, "element", NodeValue::String("Ice".into()));
+    let weakness = builder.add(mob, "weakness", NodeValue::Empty);
+    let poison = builder.add(weakness, "Poison", NodeValue::Empty);
+    builder.add(poison, "damage", NodeValue::Integer(12));
+    open(builder)
+}
+
+#[test]
+fn structs() {
+    let file = mob();
+    let mob: Mob = from_node(file.root().get("mob").unwrap()).unwrap();
+    assert_eq!(mob, Mob {
+        name: "Snail",
+        level: 1,
+        speed: None,
+        boss: Some(false),
+        origin: (-3, 7),
+        skills: vec![100, 200, 300],
+        element: Element::Ice,
+        weakness: Element::Poison { damage: 12 },
+    });
+}
+
+#[test]
+fn gaps() {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    for &(name, value) in &[("3", 30), ("0", 0), ("10", 100), ("1", 10)] {
+        builder.add(root, name, NodeValue::Integer(value));
+    }
+    builder.add(root, "other", NodeValue::Integer(-1));
+    let file = open(builder);
+    let values: Vec<Option<i64>> = from_node(file.root()).unwrap();
+    let mut expected = vec![None; 11];
+    expected[0] = Some(0);
+    expected[1] = Some(10);
+    expected[3] = Some(30);
+    expected[10] = Some(100);
+    assert_eq!(values, expected);
+    match from_node::<Vec<i64>>(file.root()) {
+        Err(Error::Deserialize { path, .. }) => assert_eq!(path.as_deref(), Some("2")),
+        result => panic!("expected the gap to be rejected, got {:?}", result),
+    }
+}
+
+#[test]
+fn mismatch() {
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Wrong {
+        skills: Vec<String>,
+    }
+    let file = mob();
+    match from_node::<Wrong>(file.root().get("mob").unwrap()) {
+        Err(Error::Deserialize { path, reason }) => {
+            assert_eq!(path.as_deref(), Some("mob/skills/0"));
+            assert!(reason.contains("expected a string"), "{}", reason);
+        },
+        result => panic!("expected a type mismatch, got {:?}", result),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Stance {
+    Stand,
+    Jump,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Frame {
+    origin: (i32, i32),
+    label: (String, i64),
+    delay: Option<i32>,
+    z: Option<i32>,
+    stance: Stance,
+    frames: Vec<Stance>,
+}
+
+#[test]
+fn round_trip() {
+    let frame = Frame {
+        origin: (-4, 12),
+        label: ("head".into(), 3),
+        delay: None,
+        z: Some(-1),
+        stance: Stance::Jump,
+        frames: vec![Stance::Stand, Stance::Jump],
+    };
+    let file = open(to_builder(&frame).unwrap());
+    let root = file.root();
+    let origin = root.get("origin").unwrap();
+    assert_eq!((origin.dtype(), origin.vector()), (Type::Vector, Some((-4, 12))));
+    let label = root.get("label").unwrap();
+    assert_eq!(label.dtype(), Type::Empty);
+    assert_eq!(label.get("0").and_then(|node| node.string()), Some("head"));
+    assert_eq!(label.get("1").and_then(|node| node.integer()), Some(3));
+    assert!(root.get("delay").is_none());
+    assert_eq!(root.get("z").and_then(|node| node.integer()), Some(-1));
+    assert_eq!(root.get("stance").and_then(|node| node.string()), Some("Jump"));
+    let frames = root.get("frames").unwrap();
+    assert_eq!(frames.get("0").and_then(|node| node.string()), Some("Stand"));
+    assert_eq!(from_node::<Frame>(root).unwrap(), frame);
+}
+
+#[test]
+fn optional_elements() {
+    let values = vec![Some(1), None, None, Some(4)];
+    let file = open(to_builder(&values).unwrap());
+    assert_eq!(file.root().iter().len(), 2);
+    assert_eq!(from_node::<Vec<Option<i32>>>(file.root()).unwrap(), values);
+}
@@ -0,0 +1,269 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Rejecting corrupt NX files when they are opened
+extern crate nx;
+
+use std::fs;
+
+use nx::bitmap::Compression;
+use nx::file::{Strategy, Table};
+use nx::write::{Builder, NodeValue};
+use nx::{Error, GenericNode, NodeId};
+
+/// Offsets of the fields of the header.
+const NODE_COUNT: usize = 4;
+const NODE_OFFSET: usize = 8;
+const STRING_OFFSET: usize = 20;
+const BITMAP_OFFSET: usize = 32;
+const AUDIO_OFFSET: usize = 44;
+
+/// The indices of the nodes in `file`, which are sorted by name after the root.
+const ROOT: usize = 0;
+const AUDIO: usize = 1;
+const BITMAP: usize = 2;
+const STRING: usize = 3;
+
+/// Writes a valid file with a node of each type which refers to a table.
+fn file() -> Vec<u8> {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    builder.add(root, "audio", NodeValue::Audio(vec![1, 2, 3, 4]));
+    builder.add(root, "bitmap", NodeValue::bitmap(&[0; 4], 1, 1, Compression::Fast));
+    builder.add(root, "string", NodeValue::String("value".into()));
+    builder.to_vec().unwrap()
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn u64_at(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn set_u32(data: &mut [u8], at: usize, value: u32) {
+    data[at..at + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn set_u64(data: &mut [u8], at: usize, value: u64) {
+    data[at..at + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Gets the offset of a node in the node table.
+fn node(data: &[u8], index: usize) -> usize {
+    u64_at(data, NODE_OFFSET) as usize + index * 20
+}
+
+/// Opens `data` after `corrupt` has changed it, returning the error.
+fn open<F: FnOnce(&mut Vec<u8>)>(corrupt: F) -> Error {
+    let mut data = file();
+    corrupt(&mut data);
+    match nx::File::from_bytes(data) {
+        Ok(_) => panic!("the corrupt file was opened"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn valid() {
+    nx::File::from_bytes(file()).unwrap();
+}
+
+#[test]
+fn invalid_magic() {
+    assert!(matches!(open(|data| data[0] = b'X'), Error::InvalidMagic));
+}
+
+#[test]
+fn too_short() {
+    assert!(matches!(open(|data| data.truncate(16)), Error::TooShort));
+}
+
+#[test]
+fn table_out_of_bounds() {
+    let err = open(|data| set_u32(data, NODE_COUNT, u32::MAX));
+    assert!(matches!(err, Error::TableOutOfBounds { table: Table::Node, .. }));
+    let err = open(|data| {
+        let len = data.len() as u64;
+        set_u64(data, STRING_OFFSET, len)
+    });
+    assert!(matches!(err, Error::TableOutOfBounds { table: Table::String, .. }));
+    let err = open(|data| set_u64(data, BITMAP_OFFSET, u64::MAX - 4));
+    assert!(matches!(err, Error::TableOutOfBounds { table: Table::Bitmap, .. }));
+    let err = open(|data| {
+        let len = data.len() as u64;
+        set_u64(data, AUDIO_OFFSET, len - 4)
+    });
+    assert!(matches!(err, Error::TableOutOfBounds { table: Table::Audio, .. }));
+}
+
+#[test]
+fn no_root() {
+    assert!(matches!(open(|data| set_u32(data, NODE_COUNT, 0)), Error::NoRoot));
+}
+
+#[test]
+fn children_out_of_bounds() {
+    let err = open(|data| {
+        let at = node(data, ROOT) + 8;
+        data[at..at + 2].copy_from_slice(&4u16.to_le_bytes());
+    });
+    assert!(matches!(err, Error::ChildrenOutOfBounds { node: 0, children: 1, count: 4 }));
+}
+
+#[test]
+fn bad_string_index() {
+    let err = open(|data| {
+        let at = node(data, AUDIO);
+        set_u32(data, at, 100)
+    });
+    assert!(matches!(err, Error::BadStringIndex { node: 1, index: 100, .. }));
+    let err = open(|data| {
+        let at = node(data, STRING) + 12;
+        set_u32(data, at, 100)
+    });
+    assert!(matches!(err, Error::BadStringIndex { node: 3, index: 100, .. }));
+}
+
+#[test]
+fn bad_bitmap_index() {
+    let err = open(|data| {
+        let at = node(data, BITMAP) + 12;
+        set_u32(data, at, 1)
+    });
+    assert!(matches!(err, Error::BadBitmapIndex { node: 2, index: 1, count: 1 }));
+}
+
+#[test]
+fn bad_audio_index() {
+    let err = open(|data| {
+        let at = node(data, AUDIO) + 12;
+        set_u32(data, at, 1)
+    });
+    assert!(matches!(err, Error::BadAudioIndex { node: 1, index: 1, count: 1 }));
+}
+
+#[test]
+fn string_out_of_bounds() {
+    let err = open(|data| {
+        let (at, len) = (u64_at(data, STRING_OFFSET) as usize, data.len() as u64);
+        set_u64(data, at, len - 1)
+    });
+    assert!(matches!(err, Error::StringOutOfBounds { index: 0, .. }));
+}
+
+#[test]
+fn bitmap_out_of_bounds() {
+    let err = open(|data| {
+        let at = u64_at(data, BITMAP_OFFSET) as usize;
+        let at = u64_at(data, at) as usize;
+        set_u32(data, at, u32::MAX)
+    });
+    assert!(matches!(err, Error::BitmapOutOfBounds { index: 0, .. }));
+}
+
+#[test]
+fn audio_out_of_bounds() {
+    let err = open(|data| {
+        let at = node(data, AUDIO) + 16;
+        set_u32(data, at, u32::MAX)
+    });
+    assert!(matches!(err, Error::AudioOutOfBounds { node: 1, length: u32::MAX, .. }));
+}
+
+#[test]
+fn invalid_utf8() {
+    let mut index = 0;
+    let err = open(|data| {
+        index = u32_at(data, node(data, STRING) + 12);
+        let table = u64_at(data, STRING_OFFSET) as usize;
+        let at = u64_at(data, table + index as usize * 8) as usize;
+        data[at + 2] = 0xFF;
+    });
+    match err {
+        Error::InvalidUtf8 { index: found } => assert_eq!(found, index),
+        err => panic!("expected invalid UTF-8, got {:?}", err),
+    }
+}
+
+#[test]
+fn unknown_type() {
+    let err = open(|data| {
+        let at = node(data, ROOT) + 10;
+        data[at..at + 2].copy_from_slice(&7u16.to_le_bytes());
+    });
+    assert!(matches!(err, Error::UnknownType { node: 0, dtype: 7 }));
+}
+
+/// A copy of `file` in the temporary directory, which is removed when this is dropped.
+struct Temp(std::path::PathBuf);
+
+impl Temp {
+    fn new(name: &str) -> Temp {
+        let path = std::env::temp_dir().join(format!("nx-{}-{}.nx", name, std::process::id()));
+        fs::write(&path, file()).unwrap();
+        Temp(path)
+    }
+}
+
+impl Drop for Temp {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn strategies() {
+    let temp = Temp::new("strategies");
+    let mmap = nx::OpenOptions::new().strategy(Strategy::Mmap).open(&temp.0).unwrap();
+    let read = nx::OpenOptions::new().strategy(Strategy::Read).open(&temp.0).unwrap();
+    assert_eq!(mmap.header_info(), read.header_info());
+    let strings = |file: &nx::File| -> Vec<_> {
+        file.root().iter().map(|node| (node.name().to_owned(), node.value().to_string()))
+            .collect()
+    };
+    assert_eq!(strings(&mmap), strings(&read));
+    drop(mmap);
+    // A file which was read keeps its contents when the file on disk is truncated.
+    fs::File::create(&temp.0).unwrap();
+    assert_eq!(read.root().get("string").and_then(|node| node.string()), Some("value"));
+    assert!(matches!(nx::OpenOptions::new().strategy(Strategy::Read).open(&temp.0),
+        Err(Error::TooShort)));
+}
+
+#[test]
+fn header_info() {
+    let data = file();
+    let file = nx::File::from_bytes(data.clone()).unwrap();
+    let info = file.header_info();
+    assert_eq!((info.node_count, info.bitmap_count, info.audio_count), (4, 1, 1));
+    // The root has an empty name, and the names of the nodes are followed by the string value.
+    assert_eq!(info.string_count, 5);
+    assert_eq!(info.node_count, file.node_count());
+    assert_eq!(info.string_count, file.string_count());
+    assert_eq!(info.bitmap_count, file.bitmap_count());
+    assert_eq!(info.audio_count, file.audio_count());
+    assert_eq!(info.node_count, u32_at(&data, NODE_COUNT));
+    assert_eq!(info.node_offset, u64_at(&data, NODE_OFFSET));
+    assert_eq!(info.string_offset, u64_at(&data, STRING_OFFSET));
+    assert_eq!(info.bitmap_offset, u64_at(&data, BITMAP_OFFSET));
+    assert_eq!(info.audio_offset, u64_at(&data, AUDIO_OFFSET));
+    // Every table is after the 52 byte header.
+    for &offset in &[info.node_offset, info.string_offset, info.bitmap_offset, info.audio_offset] {
+        assert!(offset >= 52 && offset < data.len() as u64);
+    }
+}
+
+#[test]
+fn node_ids() {
+    let file = nx::File::from_bytes(file()).unwrap();
+    assert_eq!(file.node(NodeId::ROOT).map(|node| node.id()), Some(file.root().id()));
+    for node in file.root().iter() {
+        assert_eq!(file.node(node.id()).map(|node| node.name()), Some(node.name()));
+    }
+    let last = NodeId(file.node_count() - 1);
+    assert_eq!(file.node(last).map(|node| node.name()), Some("string"));
+    assert!(file.node(NodeId(file.node_count())).is_none());
+    assert!(file.node(NodeId(u32::MAX)).is_none());
+}
@@ -0,0 +1,95 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Walking the subtree of a node
+extern crate nx;
+
+use nx::walk::{Order, Walk, Walker};
+use nx::write::{Builder, NodeValue};
+use nx::{GenericNode, Type};
+
+fn file() -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let a = builder.add(root, "a", NodeValue::Empty);
+    let a1 = builder.add(a, "a1", NodeValue::Empty);
+    builder.add(a1, "x", NodeValue::Integer(1));
+    builder.add(a, "a2", NodeValue::String("a2".into()));
+    let b = builder.add(root, "b", NodeValue::Empty);
+    builder.add(b, "b1", NodeValue::Integer(2));
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+/// Collects the path of every node in a walk, checking it against the node and its depth.
+fn paths(mut walk: Walk) -> Vec<String> {
+    let mut paths = Vec::new();
+    while let Some(node) = walk.next() {
+        assert_eq!(walk.path(), node.path());
+        assert_eq!(walk.depth(), node.depth());
+        paths.push(walk.path().to_owned());
+    }
+    paths
+}
+
+#[test]
+fn orders() {
+    let file = file();
+    let mut walker = Walker::new();
+    walker.paths(true);
+    assert_eq!(paths(walker.walk(file.root())), ["a", "a/a1", "a/a1/x", "a/a2", "b", "b/b1"]);
+    walker.order(Order::PostOrder);
+    assert_eq!(paths(walker.walk(file.root())), ["a/a1/x", "a/a1", "a/a2", "a", "b/b1", "b"]);
+    walker.order(Order::BreadthFirst);
+    assert_eq!(paths(walker.walk(file.root())), ["a", "b", "a/a1", "a/a2", "b/b1", "a/a1/x"]);
+}
+
+#[test]
+fn descendants() {
+    let file = file();
+    let names: Vec<_> = file.root().descendants().map(|node| node.name()).collect();
+    assert_eq!(names, ["a", "a1", "x", "a2", "b", "b1"]);
+    let names: Vec<_> = file.root().get("a").unwrap().descendants().map(|n| n.name()).collect();
+    assert_eq!(names, ["a1", "x", "a2"]);
+}
+
+#[test]
+fn max_depth() {
+    let file = file();
+    for &order in &[Order::PreOrder, Order::PostOrder, Order::BreadthFirst] {
+        let mut walker = Walker::new();
+        walker.order(order).paths(true).max_depth(1);
+        let mut found = paths(walker.walk(file.root()));
+        assert_eq!(found, ["a", "b"]);
+        walker.max_depth(2);
+        found = paths(walker.walk(file.root()));
+        found.sort();
+        assert_eq!(found, ["a", "a/a1", "a/a2", "b", "b/b1"], "{:?}", order);
+    }
+}
+
+#[test]
+fn prune() {
+    let file = file();
+    for &order in &[Order::PreOrder, Order::BreadthFirst] {
+        let mut walk = Walker::new().order(order).walk(file.root());
+        let mut names = Vec::new();
+        while let Some(node) = walk.next() {
+            if node.name() == "a" {
+                walk.prune();
+            }
+            names.push(node.name());
+        }
+        assert_eq!(names, ["a", "b", "b1"], "{:?}", order);
+    }
+}
+
+#[test]
+fn root_and_dtypes() {
+    let file = file();
+    let mut walker = Walker::new();
+    walker.paths(true).include_root(true);
+    assert_eq!(paths(walker.walk(file.root())).first().map(|path| &**path), Some(""));
+    assert_eq!(paths(walker.walk(file.root())).len(), 7);
+    walker.dtypes(&[Type::Integer]);
+    assert_eq!(paths(walker.walk(file.root())), ["a/a1/x", "b/b1"]);
+    walker.order(Order::PostOrder).dtypes(&[Type::Empty]);
+    assert_eq!(paths(walker.walk(file.root())), ["a/a1", "a", "b", ""]);
+}
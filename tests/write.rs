@@ -0,0 +1,104 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Writing NX files and reading them back
+extern crate nx;
+
+use std::fs;
+
+use nx::bitmap::Compression;
+use nx::write::{Builder, NodeValue};
+use nx::{GenericNode, Type};
+
+fn builder() -> Builder {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    builder.add(root, "empty", NodeValue::Empty);
+    builder.add(root, "integer", NodeValue::Integer(-1 << 40));
+    builder.add(root, "float", NodeValue::Float(0.25));
+    builder.add(root, "string", NodeValue::String("héllo".into()));
+    builder.add(root, "vector", NodeValue::Vector(-3, 4));
+    let pixels = (0..4 * 3 * 4).map(|i| i as u8).collect::<Vec<_>>();
+    builder.add(root, "bitmap", NodeValue::bitmap(&pixels, 4, 3, Compression::Fast));
+    builder.add(root, "high", NodeValue::bitmap(&pixels, 4, 3, Compression::High(9)));
+    builder.add(root, "highest", NodeValue::bitmap(&pixels, 4, 3, Compression::High(255)));
+    let mut audio = vec![0; 82];
+    audio[51] = 30;
+    audio.extend_from_slice(b"some audio data");
+    builder.add(root, "audio", NodeValue::Audio(audio));
+    let parent = builder.add(root, "parent", NodeValue::Integer(1));
+    for name in &["b", "a", "c", "aa", "B"] {
+        let child = builder.add(parent, *name, NodeValue::String(name.to_string()));
+        builder.add(child, "leaf", NodeValue::Empty);
+    }
+    builder
+}
+
+fn check(file: &nx::File) {
+    let pixels = (0..4 * 3 * 4).map(|i| i as u8).collect::<Vec<_>>();
+    let root = file.root();
+    assert_eq!(file.node_count(), 21);
+    assert_eq!(root.get("empty").map(|node| node.dtype()), Some(Type::Empty));
+    assert_eq!(root.get("integer").and_then(|node| node.integer()), Some(-1 << 40));
+    assert_eq!(root.get("float").and_then(|node| node.float()), Some(0.25));
+    assert_eq!(root.get("string").and_then(|node| node.string()), Some("héllo"));
+    assert_eq!(root.get("vector").and_then(|node| node.vector()), Some((-3, 4)));
+    for name in &["bitmap", "high", "highest"] {
+        let bitmap = root.get(name).and_then(|node| node.bitmap()).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (4, 3));
+        let mut out = vec![0; bitmap.len() as usize];
+        bitmap.data(&mut out);
+        assert_eq!(out, pixels);
+    }
+    let audio = root.get("audio").and_then(|node| node.audio()).unwrap();
+    assert_eq!(audio.header_bytes().len(), 82);
+    assert_eq!(&audio.header()[..], audio.header_bytes());
+    assert_eq!(audio.data(), b"some audio data");
+    let parent = root.get("parent").unwrap();
+    assert_eq!(parent.integer(), Some(1));
+    let names = parent.iter().map(|child| child.name()).collect::<Vec<_>>();
+    assert_eq!(names, ["B", "a", "aa", "b", "c"]);
+    for child in parent.iter() {
+        assert_eq!(child.string(), Some(child.name()));
+        assert_eq!(child.get("leaf").map(|node| node.dtype()), Some(Type::Empty));
+    }
+}
+
+#[test]
+fn write_to_vec() {
+    let file = nx::File::from_bytes(builder().to_vec().unwrap()).unwrap();
+    check(&file);
+}
+
+#[test]
+fn write_to_file() {
+    let path = std::env::temp_dir().join(format!("nx-write-{}.nx", std::process::id()));
+    builder().write(fs::File::create(&path).unwrap()).unwrap();
+    let file = nx::File::open(&path);
+    fs::remove_file(&path).unwrap();
+    check(&file.unwrap());
+}
+
+#[test]
+fn unknown_types() {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    builder.add(root, "unknown", NodeValue::Unknown(9, *b"raw data"));
+    let data = builder.to_vec().unwrap();
+    match nx::File::from_bytes(data.clone()) {
+        Err(nx::Error::UnknownType { node: 1, dtype: 9 }) => (),
+        result => panic!("expected the unknown type to be rejected, got {:?}", result.err()),
+    }
+    let file = nx::OpenOptions::new().allow_unknown_types(true).open_bytes(data).unwrap();
+    let node = file.root().get("unknown").unwrap();
+    assert_eq!(node.dtype(), Type::Unknown(9));
+    assert_eq!((node.raw_type(), node.raw_data()), (9, *b"raw data"));
+}
+
+#[test]
+fn type_codes() {
+    assert_eq!((Type::Empty.raw(), Type::String.raw(), Type::Audio.raw()), (0, 3, 6));
+    assert_eq!(Type::from_raw(5), Type::Bitmap);
+    assert_eq!(Type::from_raw(7), Type::Unknown(7));
+    for code in 0..16 {
+        assert_eq!(Type::from_raw(code).raw(), code);
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Reading hand-crafted WZ archives
+#![cfg(feature = "wz")]
+extern crate nx;
+
+use nx::wz::{self, Encryption, Value};
+
+/// The start of the GMS key, which is the initialization vector encrypted over and over with
+/// AES-256 in ECB mode.
+const GMS_KEY: [u8; 128] = [
+    0x96, 0xae, 0x3f, 0xa4, 0x48, 0xfa, 0xdd, 0x90, 0x46, 0x76, 0x05, 0x61, 0x97, 0xce, 0x78, 0x68,
+    0x2b, 0xa0, 0x44, 0x8f, 0xc1, 0x56, 0x7e, 0x32, 0xfc, 0xe1, 0xf5, 0xb3, 0x14, 0x14, 0xc5, 0x22,
+    0xf5, 0xc3, 0x68, 0x2e, 0x9d, 0xc3, 0x4a, 0x0b, 0xfa, 0xfe, 0x68, 0x45, 0x53, 0x8a, 0xfb, 0x5d,
+    0x09, 0x4f, 0x59, 0xfc, 0xe9, 0x11, 0x12, 0x9b, 0xd9, 0x0f, 0xf2, 0xe8, 0x62, 0x69, 0x3b, 0x76,
+    0x47, 0x88, 0x10, 0x75, 0xac, 0xe3, 0x96, 0xd7, 0xdb, 0x12, 0x79, 0xcd, 0x59, 0xe4, 0xe0, 0x0c,
+    0xec, 0xa1, 0xfc, 0xd2, 0xb2, 0x3c, 0xbc, 0x4a, 0x5b, 0x9d, 0x6b, 0xa9, 0x86, 0x29, 0x28, 0x82,
+    0x0b, 0x43, 0x59, 0x40, 0xda, 0xe7, 0xe5, 0x93, 0x95, 0xdf, 0x00, 0x02, 0xeb, 0x8a, 0xbe, 0x0c,
+    0xf1, 0x10, 0xaa, 0x5b, 0x67, 0x94, 0x90, 0xf4, 0xf5, 0xce, 0xe8, 0xe1, 0x61, 0x09, 0x11, 0x09,
+];
+
+/// The hash of version 83 used to encrypt offsets, and the version stored in the header.
+const V83_HASH: u32 = 1876;
+const V83_ENCRYPTED: u16 = 0xAC;
+
+/// Where the archive data starts, right after the header.
+const START: u32 = 16;
+
+/// Writes an archive the way the reader expects it, encrypting strings with `key`.
+struct Archive {
+    data: Vec<u8>,
+    key: Vec<u8>,
+}
+
+impl Archive {
+    fn new(key: &[u8], version: u16) -> Archive {
+        let mut archive = Archive { data: b"PKG1".to_vec(), key: key.to_vec() };
+        archive.bytes(&[0; 8]);
+        archive.bytes(&START.to_le_bytes());
+        archive.bytes(&version.to_le_bytes());
+        archive
+    }
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+    fn compressed(&mut self, value: i32) {
+        if value > -128 && value < 128 {
+            self.data.push(value as u8);
+        } else {
+            self.data.push(0x80);
+            self.bytes(&value.to_le_bytes());
+        }
+    }
+    fn key(&self, i: usize) -> u8 {
+        self.key.get(i).cloned().unwrap_or(0)
+    }
+    fn ascii(&mut self, string: &str) {
+        let len = string.len();
+        if len < 128 {
+            self.data.push((-(len as i32)) as u8);
+        } else {
+            self.data.push(0x80);
+            self.bytes(&(len as i32).to_le_bytes());
+        }
+        for (i, byte) in string.bytes().enumerate() {
+            let byte = byte ^ 0xAAu8.wrapping_add(i as u8) ^ self.key(i);
+            self.data.push(byte);
+        }
+    }
+    fn utf16(&mut self, string: &str) {
+        let units = string.encode_utf16().collect::<Vec<_>>();
+        if units.len() < 127 {
+            self.data.push(units.len() as u8);
+        } else {
+            self.data.push(127);
+            self.bytes(&(units.len() as i32).to_le_bytes());
+        }
+        for (i, unit) in units.into_iter().enumerate() {
+            let key = u16::from_le_bytes([self.key(i * 2), self.key(i * 2 + 1)]);
+            let unit = unit ^ 0xAAAAu16.wrapping_add(i as u16) ^ key;
+            self.bytes(&unit.to_le_bytes());
+        }
+    }
+    /// Writes a directory entry, returning where its offset goes so it can be filled in once the
+    /// entry has been written.
+    fn entry(&mut self, kind: u8, name: &str) -> usize {
+        self.data.push(kind);
+        self.ascii(name);
+        self.compressed(0);
+        self.compressed(0);
+        self.bytes(&[0; 4]);
+        self.data.len() - 4
+    }
+    /// Encrypts `target` as the offset stored at `at`.
+    fn offset(&mut self, at: usize, target: usize, hash: u32) {
+        let mut offset = (at as u32).wrapping_sub(START) ^ 0xFFFFFFFF;
+        offset = offset.wrapping_mul(hash).wrapping_sub(0x581C3F6D);
+        offset = offset.rotate_left(offset & 0x1F);
+        offset ^= (target as u32).wrapping_sub(START * 2);
+        self.data[at..at + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+    fn image_header(&mut self, count: i32) -> usize {
+        let image = self.data.len();
+        self.data.push(0x73);
+        self.ascii("Property");
+        self.bytes(&[0; 2]);
+        self.compressed(count);
+        image
+    }
+}
+
+#[test]
+fn gms_archive() {
+    let long = "a directory entry with a name long enough that the string mask wraps past 0xFF \
+        partway through it";
+    assert!(long.len() > 86 && long.len() <= GMS_KEY.len());
+    let unicode = "héllo wörld ✓";
+    let mut archive = Archive::new(&GMS_KEY, V83_ENCRYPTED);
+    archive.compressed(2);
+    let sub = archive.entry(3, "Sub");
+    let image = archive.entry(4, long);
+    let target = archive.data.len();
+    archive.offset(sub, target, V83_HASH);
+    archive.compressed(0);
+    let target = archive.data.len();
+    archive.offset(image, target, V83_HASH);
+    let start = archive.image_header(4);
+    archive.data.push(0x00);
+    archive.ascii("ascii");
+    archive.data.push(8);
+    archive.data.push(0x00);
+    let hello = archive.data.len();
+    archive.ascii("Hello");
+    archive.data.push(0x00);
+    archive.ascii("utf16");
+    archive.data.push(8);
+    archive.data.push(0x00);
+    archive.utf16(unicode);
+    archive.data.push(0x00);
+    archive.ascii("int");
+    archive.data.push(3);
+    archive.compressed(-123456);
+    archive.data.push(0x00);
+    archive.ascii("ref");
+    archive.data.push(8);
+    archive.data.push(0x01);
+    archive.bytes(&((hello - start) as i32).to_le_bytes());
+
+    let root = wz::parse(&archive.data, Encryption::Gms, None).unwrap();
+    let names = root.children.iter().map(|child| &*child.name).collect::<Vec<_>>();
+    assert_eq!(names, ["Sub", long]);
+    assert!(root.children[0].children.is_empty());
+    let image = &root.children[1];
+    assert_eq!(image.get("ascii").unwrap().value, Value::String("Hello".into()));
+    assert_eq!(image.get("utf16").unwrap().value, Value::String(unicode.into()));
+    assert_eq!(image.get("int").unwrap().value, Value::Integer(-123456));
+    assert_eq!(image.get("ref").unwrap().value, Value::String("Hello".into()));
+
+    // Offsets only decrypt to the right entries with the right version, and the wrong key
+    // garbles every string.
+    assert!(wz::parse(&archive.data, Encryption::Gms, Some(84)).is_err());
+    assert!(wz::parse(&archive.data, Encryption::Kms, None).is_err());
+}
+
+#[test]
+fn long_strings() {
+    let ascii = "0123456789".repeat(30);
+    let unicode = "ü".repeat(22000);
+    let mut archive = Archive::new(&[], V83_ENCRYPTED);
+    archive.compressed(1);
+    let image = archive.entry(4, &ascii);
+    let target = archive.data.len();
+    archive.offset(image, target, V83_HASH);
+    archive.image_header(1);
+    archive.data.push(0x00);
+    archive.ascii("utf16");
+    archive.data.push(8);
+    archive.data.push(0x00);
+    archive.utf16(&unicode);
+
+    let root = wz::parse(&archive.data, Encryption::None, Some(83)).unwrap();
+    let image = &root.children[0];
+    assert_eq!(image.name, ascii);
+    assert_eq!(image.get("utf16").unwrap().value, Value::String(unicode));
+}
+
+#[test]
+fn directory_loop() {
+    let mut archive = Archive::new(&[], V83_ENCRYPTED);
+    let directory = archive.data.len();
+    archive.compressed(1);
+    let entry = archive.entry(3, "Loop");
+    archive.offset(entry, directory, V83_HASH);
+    match wz::parse(&archive.data, Encryption::None, Some(83)) {
+        Err(nx::Error::InvalidWz { reason, .. }) => {
+            assert_eq!(reason, "directory entry points at an entry already read")
+        },
+        result => panic!("expected the loop to be rejected, got {:?}", result),
+    }
+}
+
+#[test]
+fn invalid_entry() {
+    let mut archive = Archive::new(&[], V83_ENCRYPTED);
+    archive.compressed(1);
+    archive.data.push(9);
+    let version = match wz::parse(&archive.data, Encryption::None, Some(83)) {
+        Err(nx::Error::InvalidWz { version, reason, .. }) => {
+            assert_eq!(reason, "unknown directory entry type");
+            version
+        },
+        result => panic!("expected the entry to be rejected, got {:?}", result),
+    };
+    assert_eq!(version, Some(83));
+    // Every version matching the header fails, and the error is the one from the first, which
+    // is 83.
+    match wz::parse(&archive.data, Encryption::None, None) {
+        Err(nx::Error::InvalidWz { version, reason, .. }) => {
+            assert_eq!((version, reason), (Some(83), "unknown directory entry type"))
+        },
+        result => panic!("expected the entry to be rejected, got {:?}", result),
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright © 2015-2018, Peter Atashian
+//! Exporting to XML and importing the result again
+#![cfg(feature = "xml")]
+extern crate nx;
+
+use nx::bitmap::Compression;
+use nx::export::xml;
+use nx::import::xml as import;
+use nx::import::ImportOptions;
+use nx::write::{Builder, NodeValue};
+use nx::{Error, GenericNode};
+
+fn file(string: &str) -> nx::File {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    builder.add(root, "string", NodeValue::String(string.into()));
+    builder.add(root, "vector", NodeValue::Vector(-1, 2));
+    builder.add(root, "long", NodeValue::Integer(1 << 40));
+    builder.add(root, "double", NodeValue::Float(0.1));
+    nx::File::from_bytes(builder.to_vec().unwrap()).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let string = "tab\tline\nreturn\r <&> \"quoted\" ünïcödé";
+    let file = file(string);
+    let exported = xml::to_string(file.root()).unwrap();
+    let imported = import::from_str(&exported).unwrap();
+    let file = nx::File::from_bytes(imported.to_vec().unwrap()).unwrap();
+    assert_eq!(xml::to_string(file.root()).unwrap(), exported);
+    let root = file.root();
+    assert_eq!(root.get("string").and_then(|node| node.string()), Some(string));
+    assert_eq!(root.get("vector").and_then(|node| node.vector()), Some((-1, 2)));
+    assert_eq!(root.get("long").and_then(|node| node.integer()), Some(1 << 40));
+    assert_eq!(root.get("double").and_then(|node| node.float()), Some(0.1));
+}
+
+#[test]
+fn control_characters() {
+    let file = file("x\u{1}y");
+    match xml::to_string(file.root()) {
+        Err(Error::XmlCharacter { path, character }) => {
+            assert_eq!((&*path, character), ("string", '\u{1}'))
+        },
+        result => panic!("expected the control character to be rejected, got {:?}", result),
+    }
+}
+
+fn bitmap(file: &nx::File) -> Option<nx::bitmap::Bitmap<'_>> {
+    file.root().resolve("images/bitmap").and_then(|node| node.bitmap())
+}
+
+#[test]
+fn copy_from_base() {
+    let mut builder = Builder::new();
+    let root = builder.root();
+    let pixels = [0x10, 0x20, 0x30, 0xFF].repeat(6);
+    let images = builder.add(root, "images", NodeValue::Empty);
+    builder.add(images, "bitmap", NodeValue::bitmap(&pixels, 3, 2, Compression::Fast));
+    let file = nx::File::from_bytes(builder.to_vec().unwrap()).unwrap();
+    let exported = xml::to_string(file.root()).unwrap();
+    assert!(!exported.contains("basedata"));
+    assert!(import::from_str(&exported).is_err());
+    let imported = ImportOptions::new().base(file.root()).read_xml_str(&exported).unwrap();
+    let copy = nx::File::from_bytes(imported.to_vec().unwrap()).unwrap();
+    assert_eq!(bitmap(&copy).unwrap(), bitmap(&file).unwrap());
+}